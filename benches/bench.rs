@@ -4,6 +4,7 @@ extern crate test;
 
 use std::fs::{self, File};
 use std::io::BufReader;
+use std::time::Instant;
 
 use test::{Bencher, black_box};
 
@@ -63,6 +64,26 @@ fn bench_bytes_read(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn bench_pixels_buf_read(b: &mut Bencher) {
+    b.iter(|| {
+        let file = File::open("multibot.qoi").unwrap();
+        let buf_reader = BufReader::new(file);
+        let (_header, decoder) = okay::Decoder::new_from_buf_reader(buf_reader).decode_header().unwrap();
+        black_box(decoder.decode_pixels_vec().unwrap());
+    });
+}
+
+#[bench]
+fn bench_bytes_buf_read(b: &mut Bencher) {
+    b.iter(|| {
+        let file = File::open("multibot.qoi").unwrap();
+        let buf_reader = BufReader::new(file);
+        let (_header, decoder) = okay::Decoder::new_from_buf_reader(buf_reader).decode_header().unwrap();
+        black_box(decoder.decode_bytes_vec(okay::Pixel::rgba).unwrap());
+    });
+}
+
 #[bench]
 fn bench_qoi_bytes_stream(b: &mut Bencher) {
     b.iter(|| {
@@ -72,3 +93,299 @@ fn bench_qoi_bytes_stream(b: &mut Bencher) {
         black_box(decoder.decode_to_vec().unwrap());
     });
 }
+
+/// Decodes `multibot.qoi` ahead of time, so that the encode benches only time encoding.
+fn multibot_pixels() -> (okay::Header, Vec<okay::Pixel>) {
+    let bytes = fs::read("multibot.qoi").unwrap();
+    let (header, decoder) = okay::Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    (header, decoder.decode_pixels_vec().unwrap())
+}
+
+#[bench]
+fn bench_encode_slice(b: &mut Bencher) {
+    let (header, pixels) = multibot_pixels();
+    b.iter(|| {
+        black_box(okay::Encoder::encode_to_vec(header.clone(), &pixels).unwrap());
+    });
+}
+
+#[bench]
+fn bench_encode_vec(b: &mut Bencher) {
+    // Encodes through the `io::Write` sink into a `Vec`, rather than through `VecByteSink`
+    let (header, pixels) = multibot_pixels();
+    b.iter(|| {
+        let mut encoder = okay::Encoder::new_to_writer(Vec::new(), header.clone())
+            .encode_header()
+            .unwrap();
+        encoder.encode_pixels(&pixels).unwrap();
+        black_box(encoder.finish().unwrap().into_inner());
+    });
+}
+
+#[bench]
+fn bench_qoi_encode(b: &mut Bencher) {
+    let (header, pixels) = multibot_pixels();
+    let (width, height) = header.dimensions();
+    let rgba = pixels.iter().flat_map(|pixel| pixel.rgba()).collect::<Vec<u8>>();
+    b.iter(|| {
+        black_box(qoi::encode_to_vec(&rgba, width, height).unwrap());
+    });
+}
+
+const SYNTHETIC_WIDTH: u32 = 512;
+const SYNTHETIC_HEIGHT: u32 = 512;
+const SYNTHETIC_PIXELS: usize = SYNTHETIC_WIDTH as usize * SYNTHETIC_HEIGHT as usize;
+
+fn synthetic_header() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"qoif");
+    bytes.extend_from_slice(&SYNTHETIC_WIDTH.to_be_bytes());
+    bytes.extend_from_slice(&SYNTHETIC_HEIGHT.to_be_bytes());
+    bytes.extend_from_slice(&[0x04, 0x00]);
+    bytes
+}
+
+fn end_synthetic(mut bytes: Vec<u8>) -> Vec<u8> {
+    bytes.extend_from_slice(&okay::Header::END_MARKER);
+    bytes
+}
+
+/// An image made entirely of maximum-length QOI_OP_RUN chunks; the best case for the decoder.
+fn synthetic_runs() -> Vec<u8> {
+    let mut bytes = synthetic_header();
+    let mut remaining = SYNTHETIC_PIXELS;
+    while remaining > 0 {
+        let run = remaining.min(62);
+        bytes.push(0xC0 | (run as u8 - 1));
+        remaining -= run;
+    }
+    end_synthetic(bytes)
+}
+
+/// An image made entirely of QOI_OP_RGBA chunks with pseudo-random values; the worst case for the
+/// decoder.
+fn synthetic_literals() -> Vec<u8> {
+    let mut bytes = synthetic_header();
+    let mut state = 0x2545F491u32;
+    for _ in 0..SYNTHETIC_PIXELS {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        bytes.push(0xFF);
+        bytes.extend_from_slice(&state.to_be_bytes());
+    }
+    end_synthetic(bytes)
+}
+
+/// An image which fills all 64 index slots with QOI_OP_RGBA chunks, then consists entirely of
+/// QOI_OP_INDEX chunks cycling through the slots.
+fn synthetic_indexed() -> Vec<u8> {
+    let mut bytes = synthetic_header();
+    // The hashes of (i, 0, 0, 255) for i in 0..64 cover every slot, because 3 and 64 are coprime
+    for i in 0..64 {
+        bytes.extend_from_slice(&[0xFF, i, 0, 0, 0xFF]);
+    }
+    for i in 0..(SYNTHETIC_PIXELS - 64) {
+        bytes.push((i * 7 % 64) as u8);
+    }
+    end_synthetic(bytes)
+}
+
+/// Returns a simple xorshift pseudo-random number generator.
+fn xorshift(mut state: u32) -> impl FnMut() -> u32 {
+    move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    }
+}
+
+/// An image made entirely of QOI_OP_RGB chunks with pseudo-random values.
+fn synthetic_rgb() -> Vec<u8> {
+    let mut bytes = synthetic_header();
+    let mut rng = xorshift(0x6C078965);
+    for _ in 0..SYNTHETIC_PIXELS {
+        let [_, r, g, b] = rng().to_be_bytes();
+        bytes.extend_from_slice(&[0xFE, r, g, b]);
+    }
+    end_synthetic(bytes)
+}
+
+/// An image made entirely of QOI_OP_DIFF chunks with pseudo-random differences.
+fn synthetic_diffs() -> Vec<u8> {
+    let mut bytes = synthetic_header();
+    let mut rng = xorshift(0x9908B0DF);
+    for _ in 0..SYNTHETIC_PIXELS {
+        bytes.push(0x40 | (rng() as u8 & 0x3F));
+    }
+    end_synthetic(bytes)
+}
+
+/// An image made entirely of QOI_OP_LUMA chunks with pseudo-random differences.
+fn synthetic_lumas() -> Vec<u8> {
+    let mut bytes = synthetic_header();
+    let mut rng = xorshift(0x1B873593);
+    for _ in 0..SYNTHETIC_PIXELS {
+        let [_, _, dg, drb] = rng().to_be_bytes();
+        bytes.extend_from_slice(&[0x80 | (dg & 0x3F), drb]);
+    }
+    end_synthetic(bytes)
+}
+
+/// Prints a rough breakdown of how long the decoder spends on each kind of chunk, by timing images
+/// made entirely of one kind of chunk, whose chunk counts are known from how they were generated,
+/// and dividing the decode time by the number of chunks. Real images mix chunk kinds, which changes
+/// how well branches are predicted, so these figures are only a guide to which chunks are
+/// relatively expensive. Run with `--nocapture` to see the table.
+#[bench]
+fn bench_op_breakdown(b: &mut Bencher) {
+    const ITERATIONS: usize = 20;
+
+    let images = [
+        ("QOI_OP_RGB", synthetic_rgb(), SYNTHETIC_PIXELS),
+        ("QOI_OP_RGBA", synthetic_literals(), SYNTHETIC_PIXELS),
+        ("QOI_OP_INDEX", synthetic_indexed(), SYNTHETIC_PIXELS - 64),
+        ("QOI_OP_DIFF", synthetic_diffs(), SYNTHETIC_PIXELS),
+        ("QOI_OP_LUMA", synthetic_lumas(), SYNTHETIC_PIXELS),
+        ("QOI_OP_RUN", synthetic_runs(), (SYNTHETIC_PIXELS + 61) / 62),
+    ];
+
+    let decode = |bytes: &[u8]| {
+        let (_header, decoder) = okay::Decoder::new_from_slice(bytes).decode_header().unwrap();
+        black_box(decoder.decode_pixels_vec().unwrap());
+    };
+
+    println!();
+    println!("{:<14}{:>10}{:>12}{:>14}", "chunk", "chunks", "ns/chunk", "ns/pixel");
+
+    for (name, bytes, num_chunks) in &images {
+        let best = (0..ITERATIONS)
+            .map(|_| {
+                let start = Instant::now();
+                decode(bytes);
+                start.elapsed()
+            })
+            .min()
+            .unwrap();
+
+        let nanos = best.as_nanos() as f64;
+        println!(
+            "{:<14}{:>10}{:>12.2}{:>14.2}",
+            name,
+            num_chunks,
+            nanos / *num_chunks as f64,
+            nanos / SYNTHETIC_PIXELS as f64
+        );
+    }
+
+    b.iter(|| {
+        for (_name, bytes, _num_chunks) in &images {
+            decode(bytes);
+        }
+    });
+}
+
+#[bench]
+fn bench_synthetic_runs(b: &mut Bencher) {
+    let bytes = synthetic_runs();
+    b.iter(|| {
+        let (_header, decoder) = okay::Decoder::new_from_slice(&bytes).decode_header().unwrap();
+        black_box(decoder.decode_pixels_vec().unwrap());
+    });
+}
+
+#[bench]
+fn bench_synthetic_literals(b: &mut Bencher) {
+    let bytes = synthetic_literals();
+    b.iter(|| {
+        let (_header, decoder) = okay::Decoder::new_from_slice(&bytes).decode_header().unwrap();
+        black_box(decoder.decode_pixels_vec().unwrap());
+    });
+}
+
+#[bench]
+fn bench_synthetic_indexed(b: &mut Bencher) {
+    let bytes = synthetic_indexed();
+    b.iter(|| {
+        let (_header, decoder) = okay::Decoder::new_from_slice(&bytes).decode_header().unwrap();
+        black_box(decoder.decode_pixels_vec().unwrap());
+    });
+}
+
+/// Lots of tiny 16x16 images, where the fixed cost of setting up each decoder is most noticeable.
+fn many_small_images() -> Vec<Vec<u8>> {
+    (0..64u32)
+        .map(|seed| {
+            let rgba = (0..256u32)
+                .flat_map(|i| {
+                    let v = (i * 31 + seed * 7) as u8;
+                    [v, v / 2, (i % 5) as u8 * 40, 255]
+                })
+                .collect::<Vec<u8>>();
+            qoi::encode_to_vec(&rgba, 16, 16).unwrap()
+        })
+        .collect()
+}
+
+/// Only decodes the header of each of the images from `bench_many_small`, which includes creating
+/// the `PixelDecoder` and zeroing its index, so that the fixed cost per image can be compared with
+/// the time taken to decode the whole image.
+#[bench]
+fn bench_decoder_setup(b: &mut Bencher) {
+    let images = many_small_images();
+
+    b.iter(|| {
+        for image in &images {
+            black_box(okay::Decoder::new_from_slice(image).decode_header().unwrap());
+        }
+    });
+}
+
+#[bench]
+fn bench_many_small(b: &mut Bencher) {
+    let images = many_small_images();
+
+    let mut buf = [okay::Pixel::ZERO; 256];
+
+    b.iter(|| {
+        for image in &images {
+            let (_header, mut decoder) = okay::Decoder::new_from_slice(image).decode_header().unwrap();
+            black_box(decoder.decode_pixels_into(&mut buf).unwrap());
+        }
+    });
+}
+
+/// Decodes `multibot.qoi` through `QoiDecoder::read_image`, which writes straight into the buffer
+/// it is given.
+#[cfg(feature = "image")]
+#[bench]
+fn bench_image_read_image(b: &mut Bencher) {
+    use image::ImageDecoder;
+
+    let bytes = fs::read("multibot.qoi").unwrap();
+    b.iter(|| {
+        let decoder = okay::image::QoiDecoder::new(okay::Decoder::new_from_slice(&bytes)).unwrap();
+        let mut buf = vec![0; decoder.total_bytes() as usize];
+        decoder.read_image(&mut buf).unwrap();
+        black_box(buf);
+    });
+}
+
+/// The alternative to `bench_image_read_image`: decoding into a new vec, then copying it into the
+/// destination buffer.
+#[cfg(feature = "image")]
+#[bench]
+fn bench_image_vec_then_copy(b: &mut Bencher) {
+    let bytes = fs::read("multibot.qoi").unwrap();
+    b.iter(|| {
+        let (header, decoder) = okay::Decoder::new_from_slice(&bytes).decode_header().unwrap();
+        let decoded = match header.channels() {
+            okay::header::Channels::Rgb => decoder.decode_bytes_vec(okay::Pixel::rgb).unwrap(),
+            okay::header::Channels::Rgba => decoder.decode_bytes_vec(okay::Pixel::rgba).unwrap(),
+        };
+        let mut buf = vec![0; decoded.len()];
+        buf.copy_from_slice(&decoded);
+        black_box(buf);
+    });
+}
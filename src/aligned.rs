@@ -0,0 +1,84 @@
+//! Byte buffers whose start is aligned to a chosen boundary, for decoding straight into memory
+//! which will be handed to an API with alignment requirements, such as a Vulkan or Metal staging
+//! buffer, without first copying it out of a misaligned buffer.
+
+use alloc::collections::TryReserveError;
+use alloc::vec::Vec;
+use core::ops::{Deref, DerefMut};
+
+/// A fixed-length byte buffer whose first byte is aligned to `align()` bytes.
+///
+/// The buffer over-allocates by up to `align() - 1` bytes and uses the aligned region inside the
+/// allocation, rather than allocating with a custom layout. This keeps it free of unsafe code, at
+/// the cost of a little wasted memory for large alignments.
+pub struct AlignedBuf {
+    buf: Vec<u8>,
+    offset: usize,
+    len: usize,
+    align: usize,
+}
+
+impl AlignedBuf {
+    /// Allocates a zero-filled buffer of `len` bytes aligned to `align` bytes. Panics if `align` is
+    /// not a power of two.
+    pub fn zeroed(len: usize, align: usize) -> Result<Self, TryReserveError> {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+
+        // If this saturates, reserving fails with a capacity overflow error
+        let capacity = len.saturating_add(align - 1);
+
+        let mut buf = Vec::new();
+        buf.try_reserve_exact(capacity)?;
+        buf.resize(capacity, 0);
+
+        // The number of bytes from the start of the allocation to the next multiple of `align`
+        let offset = (buf.as_ptr() as usize).wrapping_neg() & (align - 1);
+
+        Ok(Self {
+            buf,
+            offset,
+            len,
+            align,
+        })
+    }
+
+    /// Returns the alignment of the start of the buffer, in bytes.
+    pub fn align(&self) -> usize {
+        self.align
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf[self.offset..(self.offset + self.len)]
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.buf[self.offset..(self.offset + self.len)]
+    }
+
+    /// Shortens the buffer to `len` bytes, keeping its alignment. Has no effect if `len` is not
+    /// less than the current length.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+/// Returns true if `buf` starts at an address which is a multiple of `align`, which must be a power
+/// of two.
+pub fn is_aligned(buf: &[u8], align: usize) -> bool {
+    assert!(align.is_power_of_two(), "alignment must be a power of two");
+    buf.as_ptr() as usize & (align - 1) == 0
+}
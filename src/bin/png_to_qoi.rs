@@ -0,0 +1,51 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use okay::header::{Channels, ColSpace};
+use okay::{Encoder, Header, Pixel};
+
+fn main() {
+    let mut linear = false;
+    let mut paths = Vec::new();
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--linear" => linear = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    let in_path = paths.first()
+        .expect("input file not specified");
+
+    let out_path = paths.get(1)
+        .expect("output file not specified");
+
+    let image = image::open(in_path).unwrap_or_else(|err| {
+        eprintln!("error: failed to load {}: {}", in_path, err);
+        process::exit(1);
+    });
+
+    let image = image.into_rgba8();
+    let (width, height) = image.dimensions();
+    let pixels = image.pixels().map(|pixel| Pixel::from(pixel.0)).collect::<Vec<_>>();
+
+    let channels = Channels::detect(&pixels);
+    let col_space = if linear { ColSpace::Linear } else { ColSpace::Srgb };
+    let header = Header::new(width, height, channels, col_space);
+
+    println!("{}", header);
+
+    let bytes = Encoder::encode_to_vec(header, &pixels).unwrap_or_else(|err| {
+        eprintln!("error: failed to encode image: {}", err);
+        process::exit(1);
+    });
+
+    if let Err(err) = fs::write(out_path, &bytes) {
+        eprintln!("error: failed to write {}: {}", out_path, err);
+        process::exit(1);
+    }
+
+    println!("Done!");
+}
@@ -0,0 +1,42 @@
+use std::env;
+use std::fs::File;
+use std::process;
+
+use okay::{Decoder, DecoderOptions};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let path = args.get(1)
+        .expect("input file not specified");
+
+    let file = File::open(path).unwrap_or_else(|err| {
+        eprintln!("error: failed to open {}: {}", path, err);
+        process::exit(1);
+    });
+
+    let file_size = file.metadata().map(|metadata| metadata.len()).unwrap_or_else(|err| {
+        eprintln!("error: failed to read metadata of {}: {}", path, err);
+        process::exit(1);
+    });
+
+    // Empty images are unusual but valid, so report them rather than rejecting them
+    let options = DecoderOptions::new().allow_empty(true);
+
+    let header = match Decoder::new_from_reader(file).with_options(options).decode_header() {
+        Ok((header, _)) => header,
+        Err(err) => {
+            eprintln!("error: {} is not a valid QOI file: {}", path, err);
+            process::exit(1);
+        }
+    };
+
+    let num_pixels = u64::from(header.width()) * u64::from(header.height());
+
+    println!("width:       {}", header.width());
+    println!("height:      {}", header.height());
+    println!("channels:    {}", header.channels());
+    println!("color space: {}", header.col_space());
+    println!("pixels:      {}", num_pixels);
+    println!("file size:   {} bytes", file_size);
+}
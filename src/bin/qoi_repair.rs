@@ -0,0 +1,78 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use okay::{Decoder, Header};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let in_path = args.get(1)
+        .expect("input file not specified");
+
+    let out_path = args.get(2)
+        .expect("output file not specified");
+
+    let width_override = args.get(3)
+        .map(|width| width.parse::<u32>().expect("invalid width"));
+
+    let bytes = fs::read(in_path).unwrap();
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+
+    println!("{}", header);
+
+    let (pixels, found_end_marker, chunks_end) = decoder.decode_pixels_vec_lenient().unwrap();
+    let num_pixels = pixels.len() as u64;
+
+    if !found_end_marker {
+        eprintln!("warning: end marker not found, the pixel data appears to be truncated");
+    }
+
+    let claimed_pixels = header.width() as u64 * header.height() as u64;
+
+    println!("Header claims {} pixels, found {} pixels", claimed_pixels, num_pixels);
+
+    let width = width_override.unwrap_or_else(|| header.width());
+
+    let height = if width == 0 || num_pixels % width as u64 != 0 {
+        None
+    } else {
+        u32::try_from(num_pixels / width as u64).ok()
+    };
+
+    let height = match height {
+        Some(height) => height,
+        None => {
+            eprintln!(
+                "error: {} pixels cannot be arranged into rows of width {}, try specifying a width",
+                num_pixels, width
+            );
+            process::exit(1);
+        }
+    };
+
+    if (width, height) == header.dimensions() {
+        println!("Header dimensions are already consistent with the pixel data");
+    } else {
+        println!("Rewriting dimensions from {}x{} to {}x{}", header.width(), header.height(), width, height);
+    }
+
+    let mut out = Vec::with_capacity(bytes.len() + Header::END_MARKER.len());
+    out.extend_from_slice(&bytes[..4]);
+    out.extend_from_slice(&width.to_be_bytes());
+    out.extend_from_slice(&height.to_be_bytes());
+
+    if found_end_marker {
+        out.extend_from_slice(&bytes[12..]);
+    } else {
+        // Drop the bytes of any chunk cut off by the truncation, so that the end marker directly
+        // follows the last complete chunk
+        let chunks_end = chunks_end.expect("slice streams count the bytes they consume");
+        out.extend_from_slice(&bytes[12..(chunks_end as usize)]);
+        out.extend_from_slice(&Header::END_MARKER);
+    }
+
+    fs::write(out_path, out).unwrap();
+
+    println!("Done!");
+}
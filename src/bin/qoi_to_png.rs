@@ -15,10 +15,10 @@ fn main() {
     let out_path = args.get(2)
         .expect("output file not specified");
 
-    let bytes = fs::read(&in_path).unwrap();
+    let bytes = fs::read(in_path).unwrap();
     let (header, decoder) = Decoder::new_from_iter(bytes).decode_header().unwrap();
     
-    println!("{:?}", header);
+    println!("{}", header);
 
     let rgba = decoder.decode_bytes_vec(Pixel::rgba).unwrap();
 
@@ -27,7 +27,7 @@ fn main() {
 
     let encoder = image::codecs::png::PngEncoder::new(buf_writer);
 
-    encoder.write_image(&rgba, header.width() as u32, header.height() as u32, image::ColorType::Rgba8)
+    encoder.write_image(&rgba, header.width(), header.height(), image::ColorType::Rgba8)
         .unwrap();
 
     println!("Done!");
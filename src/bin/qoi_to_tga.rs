@@ -0,0 +1,64 @@
+use std::env;
+use std::fs;
+use std::io::{BufWriter, Write};
+
+use okay::header::Channels;
+use okay::Decoder;
+
+/// TGA image type for uncompressed true-color images.
+const TGA_UNCOMPRESSED_TRUE_COLOR: u8 = 2;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let in_path = args.get(1)
+        .expect("input file not specified");
+
+    let out_path = args.get(2)
+        .expect("output file not specified");
+
+    let bytes = fs::read(in_path).unwrap();
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+
+    println!("{}", header);
+
+    let width = u16::try_from(header.width())
+        .expect("image too wide for TGA, which supports at most 65535 pixels");
+
+    let height = u16::try_from(header.height())
+        .expect("image too tall for TGA, which supports at most 65535 pixels");
+
+    let (pixel_depth, alpha_bits) = match header.channels() {
+        Channels::Rgb => (24, 0),
+        Channels::Rgba => (32, 8),
+    };
+
+    eprintln!("Writing {}x{} uncompressed {}-bit TGA", width, height, pixel_depth);
+
+    // TGA rows are stored bottom-up by default, which is what the image descriptor below declares
+    let pixels = decoder.decode_pixels_vec_flipped(&header).unwrap();
+
+    let out_file = fs::File::create(out_path).unwrap();
+    let mut writer = BufWriter::new(out_file);
+
+    let mut tga_header = [0u8; 18];
+    tga_header[2] = TGA_UNCOMPRESSED_TRUE_COLOR;
+    tga_header[12..14].copy_from_slice(&width.to_le_bytes());
+    tga_header[14..16].copy_from_slice(&height.to_le_bytes());
+    tga_header[16] = pixel_depth;
+    tga_header[17] = alpha_bits;
+    writer.write_all(&tga_header).unwrap();
+
+    // TGA stores channels in BGR(A) order
+    for pixel in pixels {
+        match header.channels() {
+            Channels::Rgb => writer.write_all(&pixel.bgr()),
+            Channels::Rgba => writer.write_all(&pixel.bgra()),
+        }
+        .unwrap();
+    }
+
+    writer.flush().unwrap();
+
+    println!("Done!");
+}
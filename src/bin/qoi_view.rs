@@ -0,0 +1,135 @@
+use std::env;
+use std::fs;
+use std::process;
+use std::time::Duration;
+
+use minifb::{Key, KeyRepeat, ScaleMode, Window, WindowOptions};
+
+use okay::{Decoder, Pixel};
+
+/// The largest size the window is initially opened at. Larger images are scaled down to fit.
+const MAX_WINDOW_SIZE: (usize, usize) = (1280, 800);
+
+/// The width in pixels of the squares of the checkerboard which transparent images are drawn over.
+const CHECKER_SIZE: usize = 8;
+const CHECKER_LIGHT: Pixel = Pixel::new(204, 204, 204, 255);
+const CHECKER_DARK: Pixel = Pixel::new(153, 153, 153, 255);
+
+/// An image ready to be drawn to the window, as 0RGB pixels.
+struct Frame {
+    width: usize,
+    height: usize,
+    buf: Vec<u32>,
+}
+
+fn load(path: &str) -> Result<Frame, String> {
+    let bytes = fs::read(path)
+        .map_err(|err| format!("failed to read {}: {}", path, err))?;
+
+    let (header, decoder) = Decoder::new_from_slice(&bytes)
+        .decode_header()
+        .map_err(|err| format!("{} is not a valid QOI file: {}", path, err))?;
+
+    let pixels = decoder.decode_pixels_vec()
+        .map_err(|err| format!("failed to decode {}: {}", path, err))?;
+
+    let width = header.width() as usize;
+    let height = header.height() as usize;
+
+    // Composite each pixel over a checkerboard so that transparent areas are visible
+    let buf = pixels
+        .iter()
+        .enumerate()
+        .map(|(i, pixel)| {
+            let (x, y) = (i % width, i / width);
+            let checker = if (x / CHECKER_SIZE + y / CHECKER_SIZE) % 2 == 0 {
+                CHECKER_LIGHT
+            } else {
+                CHECKER_DARK
+            };
+            // The window ignores the top byte, which would be the alpha
+            pixel.over(checker).to_argb_u32()
+        })
+        .collect();
+
+    Ok(Frame { width, height, buf })
+}
+
+/// Returns the size to open the window at for an image of the given size, which preserves the image's
+/// aspect ratio but fits within `MAX_WINDOW_SIZE`.
+fn window_size(width: usize, height: usize) -> (usize, usize) {
+    let (max_width, max_height) = MAX_WINDOW_SIZE;
+    let scale = f64::min(
+        1.0,
+        f64::min(max_width as f64 / width as f64, max_height as f64 / height as f64),
+    );
+
+    (
+        ((width as f64 * scale) as usize).max(1),
+        ((height as f64 * scale) as usize).max(1),
+    )
+}
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+
+    if paths.is_empty() {
+        eprintln!("usage: qoi_view FILE...");
+        process::exit(1);
+    }
+
+    let mut current = 0;
+
+    let mut frame = load(&paths[current]).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+
+    let (window_width, window_height) = window_size(frame.width, frame.height);
+
+    let options = WindowOptions {
+        resize: true,
+        scale_mode: ScaleMode::AspectRatioStretch,
+        ..WindowOptions::default()
+    };
+
+    let mut window = Window::new(&paths[current], window_width, window_height, options)
+        .unwrap_or_else(|err| {
+            eprintln!("error: failed to open window: {}", err);
+            process::exit(1);
+        });
+
+    window.limit_update_rate(Some(Duration::from_micros(16_600)));
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        // The arrow keys cycle through the files given on the command line
+        let step = if window.is_key_pressed(Key::Right, KeyRepeat::No) {
+            1
+        } else if window.is_key_pressed(Key::Left, KeyRepeat::No) {
+            paths.len() - 1
+        } else {
+            0
+        };
+
+        // Skip over any files which fail to load, stopping if we get back to the current one
+        let mut next = (current + step) % paths.len();
+        while next != current {
+            match load(&paths[next]) {
+                Ok(next_frame) => {
+                    frame = next_frame;
+                    current = next;
+                    window.set_title(&paths[current]);
+                }
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    next = (next + step) % paths.len();
+                }
+            }
+        }
+
+        if let Err(err) = window.update_with_buffer(&frame.buf, frame.width, frame.height) {
+            eprintln!("error: failed to draw image: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::convert::Infallible;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+/// A trait representing a fallible destination for bytes, the counterpart to `ByteStream` for
+/// encoding.
+pub trait ByteSink {
+    type IoError;
+
+    /// Writes all `N` of the given bytes to the sink. Implementors of the trait can define an IO
+    /// error type, which they may return if some IO error occurs while writing the bytes.
+    fn write_n<const N: usize>(&mut self, bytes: [u8; N]) -> Result<(), Self::IoError>;
+
+    /// A specialised version of `write_n` that writes just a single byte.
+    #[inline]
+    fn write_one(&mut self, byte: u8) -> Result<(), Self::IoError> {
+        self.write_n([byte])
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub struct VecByteSink {
+    vec: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl VecByteSink {
+    pub fn new(vec: Vec<u8>) -> Self {
+        Self { vec }
+    }
+
+    pub fn inner(&self) -> &Vec<u8> {
+        &self.vec
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.vec
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<Vec<u8>> for VecByteSink {
+    fn from(vec: Vec<u8>) -> Self {
+        Self::new(vec)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ByteSink for VecByteSink {
+    // Pushing to a vec can never encounter an IO error (running out of memory aborts instead)
+    type IoError = Infallible;
+
+    #[inline]
+    fn write_n<const N: usize>(&mut self, bytes: [u8; N]) -> Result<(), Self::IoError> {
+        self.vec.extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    #[inline]
+    fn write_one(&mut self, byte: u8) -> Result<(), Self::IoError> {
+        self.vec.push(byte);
+        Ok(())
+    }
+}
+
+/// A sink which writes to an `io::Write`. Every chunk is written with a separate call to
+/// `write_all`, so unbuffered writers such as files should be wrapped in an `io::BufWriter`.
+#[cfg(feature = "std")]
+pub struct WriteByteSink<W> {
+    writer: W,
+}
+
+#[cfg(feature = "std")]
+impl<W> WriteByteSink<W>
+where
+    W: Write,
+{
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn inner(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn inner_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W> From<W> for WriteByteSink<W>
+where
+    W: Write,
+{
+    fn from(writer: W) -> Self {
+        Self::new(writer)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W> ByteSink for WriteByteSink<W>
+where
+    W: Write,
+{
+    type IoError = Box<io::Error>;
+
+    fn write_n<const N: usize>(&mut self, bytes: [u8; N]) -> Result<(), Self::IoError> {
+        self.writer.write_all(&bytes).map_err(Box::new)
+    }
+}
@@ -1,8 +1,22 @@
-use std::convert::Infallible;
+#[cfg(feature = "alloc")]
+use alloc::collections::VecDeque;
+use core::convert::Infallible;
+use core::fmt;
+#[cfg(not(feature = "safe_only"))]
+use core::mem::MaybeUninit;
+#[cfg(feature = "std")]
 use std::error;
-use std::fmt;
-use std::io::{self, Read};
-use std::mem::MaybeUninit;
+#[cfg(feature = "memmap")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, Read};
+#[cfg(feature = "memmap")]
+use std::path::Path;
+
+#[cfg(feature = "memmap")]
+use memmap2::Mmap;
+#[cfg(all(feature = "tokio", feature = "std"))]
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 /// A trait representing a fallible sequence of bytes, which may be infinite or finite.
 pub trait ByteStream {
@@ -12,6 +26,10 @@ pub trait ByteStream {
     /// the sequence, a `StreamError::UnexpectedEof` should be returned. Implmentors of the trait
     /// can also define an IO error type, which they may return if some IO error occurs while
     /// generating the bytes.
+    ///
+    /// If `UnexpectedEof` is returned without consuming any bytes, a `PixelDecoder` reading from
+    /// the stream can carry on from where it stopped once more bytes become available. Streams
+    /// which consume the bytes they did manage to read cannot be resumed in this way.
     fn read_n<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Self::IoError>>;
 
     /// A specialised version of `read_n` that returns just the next byte in the sequence.
@@ -19,15 +37,47 @@ pub trait ByteStream {
     fn read_one(&mut self) -> Result<u8, StreamError<Self::IoError>> {
         self.read_n().map(|[b]| b)
     }
+
+    /// Returns the number of bytes which have been successfully read from the stream so far. This
+    /// can be used to find where the QOI data ends when it is embedded in a larger file. Streams
+    /// which do not keep count return `None`, which is the default.
+    fn bytes_consumed(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// A byte stream which can look at its next byte without consuming it. This is a separate trait
+/// from `ByteStream` because not every stream can peek without buffering; `ReadByteStream`, for
+/// example, would need to hold on to the peeked byte and check for it on every read.
+pub trait PeekableByteStream: ByteStream {
+    /// Returns the next byte in the sequence without consuming it, or `None` if the sequence has
+    /// ended.
+    fn peek_one(&mut self) -> Result<Option<u8>, StreamError<Self::IoError>>;
 }
 
+/// A stream which holds some number of bytes in memory which can be read without waiting, but
+/// which may have more bytes to come later, such as a queue which is filled as bytes arrive from a
+/// socket. Decoders use this to avoid starting a chunk which is not entirely available yet; see
+/// `PixelDecoder::decode_pixels_into_partial`.
+pub trait BufferedByteStream: PeekableByteStream {
+    /// Returns the number of bytes which can currently be read from the stream without any of the
+    /// reads failing.
+    fn buffered_len(&self) -> usize;
+}
+
+#[derive(Clone)]
 pub struct SliceByteStream<'a> {
     slice: &'a [u8],
+    /// The length of the slice the stream was created with.
+    len: usize,
 }
 
 impl<'a> SliceByteStream<'a> {
     pub fn new(slice: &'a [u8]) -> Self {
-        Self { slice }
+        Self {
+            slice,
+            len: slice.len(),
+        }
     }
 
     pub fn inner(&self) -> &'a [u8] {
@@ -64,10 +114,29 @@ impl<'a> ByteStream for SliceByteStream<'a> {
             Ok(byte)
         }
     }
+
+    fn bytes_consumed(&self) -> Option<u64> {
+        Some((self.len - self.slice.len()) as u64)
+    }
+}
+
+impl<'a> PeekableByteStream for SliceByteStream<'a> {
+    #[inline]
+    fn peek_one(&mut self) -> Result<Option<u8>, StreamError<Self::IoError>> {
+        Ok(self.slice.first().copied())
+    }
+}
+
+impl<'a> BufferedByteStream for SliceByteStream<'a> {
+    #[inline]
+    fn buffered_len(&self) -> usize {
+        self.slice.len()
+    }
 }
 
 pub struct IterByteStream<I> {
     iter: I,
+    consumed: u64,
 }
 
 impl<I, T> IterByteStream<I>
@@ -76,7 +145,7 @@ where
     T: IntoStreamResult,
 {
     pub fn new(iter: I) -> Self {
-        Self { iter }
+        Self { iter, consumed: 0 }
     }
 
     pub fn inner(&self) -> &I {
@@ -109,6 +178,7 @@ where
 {
     type IoError = T::IoError;
 
+    #[cfg(not(feature = "safe_only"))]
     fn read_n<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Self::IoError>> {
         let mut buf = MaybeUninit::<[u8; N]>::uninit();
         let mut ptr = buf.as_mut_ptr() as *mut u8;
@@ -134,25 +204,121 @@ where
         unsafe { Ok(buf.assume_init()) }
     }
 
+    #[cfg(feature = "safe_only")]
+    fn read_n<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Self::IoError>> {
+        let mut buf = [0; N];
+        for byte in &mut buf {
+            *byte = self.read_one()?;
+        }
+        Ok(buf)
+    }
+
     #[inline]
     fn read_one(&mut self) -> Result<u8, StreamError<Self::IoError>> {
-        self.iter
+        let byte = self.iter
             .next()
             .map(T::into_stream_result)
-            .unwrap_or(Err(StreamError::UnexpectedEof))
+            .unwrap_or(Err(StreamError::UnexpectedEof))?;
+        self.consumed += 1;
+        Ok(byte)
+    }
+
+    fn bytes_consumed(&self) -> Option<u64> {
+        Some(self.consumed)
+    }
+}
+
+/// A stream which pops bytes from the front of a `VecDeque`, for decoding from a queue which more
+/// bytes are appended to as they arrive, such as packets from a network. A read which asks for more
+/// bytes than are in the queue returns `StreamError::UnexpectedEof` and leaves the queue untouched,
+/// so a decoder which runs out of bytes partway through a chunk can carry on once more are appended.
+#[cfg(feature = "alloc")]
+pub struct VecDequeByteStream<'a> {
+    deque: &'a mut VecDeque<u8>,
+    consumed: u64,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> VecDequeByteStream<'a> {
+    pub fn new(deque: &'a mut VecDeque<u8>) -> Self {
+        Self { deque, consumed: 0 }
+    }
+
+    pub fn inner(&self) -> &VecDeque<u8> {
+        self.deque
+    }
+
+    pub fn inner_mut(&mut self) -> &mut VecDeque<u8> {
+        self.deque
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<&'a mut VecDeque<u8>> for VecDequeByteStream<'a> {
+    fn from(deque: &'a mut VecDeque<u8>) -> Self {
+        Self::new(deque)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> ByteStream for VecDequeByteStream<'a> {
+    type IoError = Infallible;
+
+    fn read_n<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Self::IoError>> {
+        if self.deque.len() < N {
+            return Err(StreamError::UnexpectedEof);
+        }
+
+        let mut bytes = [0; N];
+        for (dst, src) in bytes.iter_mut().zip(self.deque.drain(..N)) {
+            *dst = src;
+        }
+
+        self.consumed += N as u64;
+        Ok(bytes)
+    }
+
+    fn read_one(&mut self) -> Result<u8, StreamError<Self::IoError>> {
+        let byte = self.deque.pop_front().ok_or(StreamError::UnexpectedEof)?;
+        self.consumed += 1;
+        Ok(byte)
+    }
+
+    fn bytes_consumed(&self) -> Option<u64> {
+        Some(self.consumed)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> PeekableByteStream for VecDequeByteStream<'a> {
+    fn peek_one(&mut self) -> Result<Option<u8>, StreamError<Self::IoError>> {
+        Ok(self.deque.front().copied())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> BufferedByteStream for VecDequeByteStream<'a> {
+    fn buffered_len(&self) -> usize {
+        self.deque.len()
     }
 }
 
+#[cfg(feature = "std")]
 pub struct ReadByteStream<R> {
     reader: R,
+    consumed: u64,
 }
 
+#[cfg(feature = "std")]
 impl<R> ReadByteStream<R>
 where
     R: Read,
 {
     pub fn new(reader: R) -> Self {
-        Self { reader }
+        Self {
+            reader,
+            consumed: 0,
+        }
     }
 
     pub fn inner(&self) -> &R {
@@ -168,6 +334,7 @@ where
     }
 }
 
+#[cfg(feature = "std")]
 impl<R> From<R> for ReadByteStream<R>
 where
     R: Read,
@@ -177,6 +344,7 @@ where
     }
 }
 
+#[cfg(feature = "std")]
 impl<R> ByteStream for ReadByteStream<R>
 where
     R: Read,
@@ -187,11 +355,361 @@ where
         let mut buf = [0; N];
         self.reader
             .read_exact(&mut buf)
-            .map(|_| buf)
             .map_err(|err| match err.kind() {
                 io::ErrorKind::UnexpectedEof => StreamError::UnexpectedEof,
                 _ => StreamError::Io(Box::new(err)),
-            })
+            })?;
+        self.consumed += N as u64;
+        Ok(buf)
+    }
+
+    /// Returns the number of bytes read by successful calls to `read_n`. If a read fails, the
+    /// number of bytes taken from the reader by that read is unknown and is not counted.
+    fn bytes_consumed(&self) -> Option<u64> {
+        Some(self.consumed)
+    }
+}
+
+/// A stream which reads directly from the buffer of an `io::BufRead`, such as an `io::BufReader`.
+/// Unlike `ReadByteStream`, which goes through `read_exact` for every read, most reads are a copy
+/// out of the reader's buffer, and the reader is only asked for more bytes once its buffer has been
+/// used up.
+#[cfg(feature = "std")]
+pub struct BufReadByteStream<R> {
+    reader: R,
+    consumed: u64,
+}
+
+#[cfg(feature = "std")]
+impl<R> BufReadByteStream<R>
+where
+    R: BufRead,
+{
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            consumed: 0,
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn inner_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns the reader's buffer, refilling it if it is empty and retrying if interrupted, or
+    /// `None` if the reader has no more bytes.
+    fn fill_buf(&mut self) -> Result<Option<&[u8]>, StreamError<Box<io::Error>>> {
+        // Returning the buffer from inside the loop would keep the reader borrowed across loop
+        // iterations, so only the length is taken here
+        let len = loop {
+            match self.reader.fill_buf() {
+                Ok(buf) => break buf.len(),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(StreamError::Io(Box::new(err))),
+            }
+        };
+
+        if len == 0 {
+            return Ok(None);
+        }
+
+        // The buffer is not empty, so this returns it without reading from the reader again
+        self.reader
+            .fill_buf()
+            .map(Some)
+            .map_err(|err| StreamError::Io(Box::new(err)))
+    }
+
+    /// Reads `N` bytes when they are split across more than one fill of the reader's buffer.
+    #[cold]
+    fn read_n_slow<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Box<io::Error>>> {
+        let mut bytes = [0; N];
+        let mut filled = 0;
+
+        while filled < N {
+            let buf = self.fill_buf()?.ok_or(StreamError::UnexpectedEof)?;
+
+            let len = buf.len().min(N - filled);
+            bytes[filled..(filled + len)].copy_from_slice(&buf[..len]);
+            self.reader.consume(len);
+            self.consumed += len as u64;
+            filled += len;
+        }
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R> From<R> for BufReadByteStream<R>
+where
+    R: BufRead,
+{
+    fn from(reader: R) -> Self {
+        Self::new(reader)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R> ByteStream for BufReadByteStream<R>
+where
+    R: BufRead,
+{
+    type IoError = Box<io::Error>;
+
+    #[inline]
+    fn read_n<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Self::IoError>> {
+        let bytes = match self.reader.fill_buf() {
+            Ok(buf) => buf.get(..N).and_then(|bytes| <[u8; N]>::try_from(bytes).ok()),
+            Err(_) => None,
+        };
+
+        match bytes {
+            Some(bytes) => {
+                self.reader.consume(N);
+                self.consumed += N as u64;
+                Ok(bytes)
+            }
+            // Errors are retried or returned by the slow path
+            None => self.read_n_slow(),
+        }
+    }
+
+    /// Returns the number of bytes read by `read_n`. If a read fails part of the way through, the
+    /// bytes it took from the reader before failing are counted.
+    fn bytes_consumed(&self) -> Option<u64> {
+        Some(self.consumed)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R> PeekableByteStream for BufReadByteStream<R>
+where
+    R: BufRead,
+{
+    fn peek_one(&mut self) -> Result<Option<u8>, StreamError<Self::IoError>> {
+        self.fill_buf().map(|buf| buf.map(|buf| buf[0]))
+    }
+}
+
+/// A stream over a memory-mapped file, which reads at close to the speed of a `SliceByteStream`
+/// without reading the whole file into memory first. Pages of the file are loaded by the operating
+/// system as they are first read.
+#[cfg(feature = "memmap")]
+pub struct MmapByteStream {
+    mmap: Mmap,
+    pos: usize,
+}
+
+#[cfg(feature = "memmap")]
+impl MmapByteStream {
+    /// Maps the file at `path` into memory.
+    ///
+    /// # Safety
+    /// The file must not be modified or truncated, by this process or any other, while the stream
+    /// exists. The stream hands out shared slices of the mapping, so a file which changes
+    /// underneath it breaks Rust's aliasing rules, and on some platforms truncating the file
+    /// crashes the process when the missing pages are read.
+    pub unsafe fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(path)?;
+        let mmap = Mmap::map(&file)?;
+        Ok(Self::from_mmap(mmap))
+    }
+
+    /// Creates a stream over an existing memory map. This lets the caller choose how the file is
+    /// mapped, and makes them responsible for upholding `Mmap::map`'s safety requirements.
+    pub fn from_mmap(mmap: Mmap) -> Self {
+        Self { mmap, pos: 0 }
+    }
+
+    /// Returns the whole mapped file, including any bytes which have already been read.
+    pub fn inner(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Returns a slice stream over the bytes which have not been read yet.
+    #[inline]
+    fn remaining(&self) -> SliceByteStream<'_> {
+        SliceByteStream::new(&self.mmap[self.pos..])
+    }
+}
+
+#[cfg(feature = "memmap")]
+impl ByteStream for MmapByteStream {
+    type IoError = Infallible;
+
+    #[inline]
+    fn read_n<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Self::IoError>> {
+        let bytes = self.remaining().read_n()?;
+        self.pos += N;
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn read_one(&mut self) -> Result<u8, StreamError<Self::IoError>> {
+        let byte = self.remaining().read_one()?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn bytes_consumed(&self) -> Option<u64> {
+        Some(self.pos as u64)
+    }
+}
+
+#[cfg(feature = "memmap")]
+impl PeekableByteStream for MmapByteStream {
+    #[inline]
+    fn peek_one(&mut self) -> Result<Option<u8>, StreamError<Self::IoError>> {
+        self.remaining().peek_one()
+    }
+}
+
+#[cfg(feature = "memmap")]
+impl BufferedByteStream for MmapByteStream {
+    #[inline]
+    fn buffered_len(&self) -> usize {
+        self.mmap.len() - self.pos
+    }
+}
+
+/// A stream which reads from a `tokio` `AsyncRead` into a buffer of its own. Reading through the
+/// `ByteStream` trait never waits for the reader: it only takes bytes which are already buffered, and
+/// returns `StreamError::UnexpectedEof` without consuming anything if there are not enough of them.
+/// The buffer is refilled with `fill`, which the async methods of `Decoder` and `PixelDecoder` call
+/// whenever they need more bytes.
+#[cfg(all(feature = "tokio", feature = "std"))]
+pub struct AsyncByteStream<R> {
+    reader: R,
+    buf: Box<[u8]>,
+    start: usize,
+    end: usize,
+    consumed: u64,
+}
+
+#[cfg(all(feature = "tokio", feature = "std"))]
+impl<R> AsyncByteStream<R>
+where
+    R: AsyncRead + Unpin,
+{
+    const BUF_LEN: usize = 8 * 1024;
+
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: vec![0; Self::BUF_LEN].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            consumed: 0,
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn inner_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the reader. Any bytes which were buffered but not read yet are lost; use `buffer`
+    /// to get them first.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns the bytes which have been read from the reader but not from the stream.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.start..self.end]
+    }
+
+    /// Reads more bytes from the reader into the buffer, waiting until at least one is available,
+    /// and returns how many were read. Returns 0 once the reader has no more bytes, or if the buffer
+    /// is already full.
+    pub async fn fill(&mut self) -> io::Result<usize> {
+        // Move the unread bytes to the front, to make as much room as possible after them
+        if self.start > 0 {
+            self.buf.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+        }
+
+        let len = self.reader.read(&mut self.buf[self.end..]).await?;
+        self.end += len;
+        Ok(len)
+    }
+
+    /// Fills the buffer until it holds at least `len` bytes, which must be no more than the size of
+    /// the buffer. Returns false if the reader ran out of bytes first.
+    pub(crate) async fn fill_to(&mut self, len: usize) -> io::Result<bool> {
+        while self.end - self.start < len {
+            if self.fill().await? == 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(all(feature = "tokio", feature = "std"))]
+impl<R> From<R> for AsyncByteStream<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn from(reader: R) -> Self {
+        Self::new(reader)
+    }
+}
+
+#[cfg(all(feature = "tokio", feature = "std"))]
+impl<R> ByteStream for AsyncByteStream<R> {
+    type IoError = Box<io::Error>;
+
+    #[inline]
+    fn read_n<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Self::IoError>> {
+        let bytes = SliceByteStream::new(&self.buf[self.start..self.end])
+            .read_n()
+            .map_err(|_| StreamError::UnexpectedEof)?;
+        self.start += N;
+        self.consumed += N as u64;
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn read_one(&mut self) -> Result<u8, StreamError<Self::IoError>> {
+        let [byte] = self.read_n()?;
+        Ok(byte)
+    }
+
+    fn bytes_consumed(&self) -> Option<u64> {
+        Some(self.consumed)
+    }
+}
+
+#[cfg(all(feature = "tokio", feature = "std"))]
+impl<R> PeekableByteStream for AsyncByteStream<R> {
+    #[inline]
+    fn peek_one(&mut self) -> Result<Option<u8>, StreamError<Self::IoError>> {
+        Ok(self.buf[self.start..self.end].first().copied())
+    }
+}
+
+#[cfg(all(feature = "tokio", feature = "std"))]
+impl<R> BufferedByteStream for AsyncByteStream<R> {
+    #[inline]
+    fn buffered_len(&self) -> usize {
+        self.end - self.start
     }
 }
 
@@ -220,6 +738,7 @@ impl<E> IntoStreamResult for Result<u8, E> {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum StreamError<E> {
     UnexpectedEof,
     Io(E),
@@ -237,4 +756,5 @@ where
     }
 }
 
+#[cfg(feature = "std")]
 impl<E> error::Error for StreamError<E> where E: error::Error {}
@@ -0,0 +1,110 @@
+//! A non-standard extension to QOI which appends a checksum to the file, for detecting corruption
+//! in storage or transit. The checksum is a 4-byte big-endian CRC-32 (the same CRC as PNG and zlib)
+//! of every byte before it: the header, the chunks and the end marker.
+//!
+//! ```text
+//! header      [u8; 14]
+//! chunks      ...
+//! end marker  [u8; 8]
+//! crc         u32      big-endian
+//! ```
+//!
+//! Unlike the `ext64` extension, files with a checksum are still valid QOI files; other decoders
+//! stop at the end marker and never see it. However, other encoders will not write a checksum, and
+//! tools which rewrite QOI files will drop it.
+//!
+//! To write the checksum, encode to a `CrcByteSink` and finish with
+//! `PixelEncoder::finish_with_crc`. To check it, decode from a `CrcByteStream` and finish with
+//! `PixelDecoder::verify_crc`.
+
+use crc32fast::Hasher;
+
+use crate::byte_sink::ByteSink;
+use crate::byte_stream::{ByteStream, StreamError};
+
+/// A byte stream which computes the CRC-32 of every byte read from the underlying stream.
+pub struct CrcByteStream<S> {
+    inner: S,
+    hasher: Hasher,
+}
+
+impl<S> CrcByteStream<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            hasher: Hasher::new(),
+        }
+    }
+
+    /// Returns the CRC-32 of the bytes read so far.
+    pub fn crc(&self) -> u32 {
+        self.hasher.clone().finalize()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> ByteStream for CrcByteStream<S>
+where
+    S: ByteStream,
+{
+    type IoError = S::IoError;
+
+    #[inline]
+    fn read_n<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Self::IoError>> {
+        let bytes = self.inner.read_n()?;
+        self.hasher.update(&bytes);
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn read_one(&mut self) -> Result<u8, StreamError<Self::IoError>> {
+        let byte = self.inner.read_one()?;
+        self.hasher.update(&[byte]);
+        Ok(byte)
+    }
+
+    fn bytes_consumed(&self) -> Option<u64> {
+        self.inner.bytes_consumed()
+    }
+}
+
+/// A byte sink which computes the CRC-32 of every byte written to the underlying sink.
+pub struct CrcByteSink<S> {
+    inner: S,
+    hasher: Hasher,
+}
+
+impl<S> CrcByteSink<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            hasher: Hasher::new(),
+        }
+    }
+
+    /// Returns the CRC-32 of the bytes written so far.
+    pub fn crc(&self) -> u32 {
+        self.hasher.clone().finalize()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> ByteSink for CrcByteSink<S>
+where
+    S: ByteSink,
+{
+    type IoError = S::IoError;
+
+    #[inline]
+    fn write_n<const N: usize>(&mut self, bytes: [u8; N]) -> Result<(), Self::IoError> {
+        self.inner.write_n(bytes)?;
+        self.hasher.update(&bytes);
+        Ok(())
+    }
+}
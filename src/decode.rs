@@ -1,27 +1,176 @@
-use std::convert;
+#[cfg(feature = "alloc")]
+use alloc::collections::TryReserveError;
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::cell::Cell;
+use core::convert;
+use core::fmt;
+#[cfg(feature = "alloc")]
+use core::iter;
+#[cfg(feature = "alloc")]
+use core::mem;
+#[cfg(not(feature = "safe_only"))]
+use core::slice;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
 use std::error;
-use std::fmt;
+#[cfg(feature = "std")]
 use std::io;
-use std::slice;
 
+#[cfg(all(feature = "tokio", feature = "std"))]
+use tokio::io::AsyncRead;
+
+#[cfg(feature = "aligned")]
+use crate::aligned::AlignedBuf;
 use crate::byte_stream::SliceByteStream;
+#[cfg(feature = "ext64")]
+use crate::ext64::Header64;
 use crate::byte_stream::{
-    ByteStream, IntoStreamResult, IterByteStream, ReadByteStream, StreamError,
+    BufferedByteStream, ByteStream, IntoStreamResult, IterByteStream, StreamError,
 };
+#[cfg(feature = "crc")]
+use crate::crc::CrcByteStream;
+#[cfg(feature = "std")]
+use crate::byte_stream::{BufReadByteStream, ReadByteStream};
+#[cfg(all(feature = "tokio", feature = "std"))]
+use crate::byte_stream::AsyncByteStream;
 use crate::header::{self, Header};
+#[cfg(feature = "alloc")]
+use crate::header::{Channels, ColSpace};
+use crate::hex::HexBytes;
 use crate::pixel::Pixel;
-use crate::pixel_index::PixelIndex;
+use crate::pixel_index::QoiIndex;
+#[cfg(feature = "alloc")]
+use crate::validate::{ValidationIssue, ValidationReport};
+#[cfg(feature = "wide_index")]
+use crate::wide_index::{self, IndexBits, WidePixelDecoder};
+
+#[cfg(feature = "alloc")]
+mod range;
+
+#[cfg(feature = "alloc")]
+pub use range::{RangeDecodeError, RangeDecoder};
+
+/// The largest number of bytes a single chunk can occupy, which is the size of a QOI_OP_RGBA chunk.
+#[cfg(debug_assertions)]
+const MAX_CHUNK_LEN: usize = 5;
 
-// TODO: "the byte stream's end is marked with 7 0x00 bytes followed by a single 0x01 byte"
+/// The length in bytes of a standard QOI header.
+#[cfg(feature = "alloc")]
+const HEADER_LEN: usize = 14;
+
+/// The longest run a single QOI_OP_RUN chunk can encode.
+#[cfg(feature = "alloc")]
+pub(crate) const MAX_RUN: u8 = 62;
 
 pub struct Decoder<S> {
     stream: S,
+    options: DecoderOptions,
+}
+
+/// Settings which control how a `Decoder` treats its input. The defaults accept any image which
+/// conforms to the QOI specification, other than images with no pixels.
+#[derive(Clone, Default, Debug)]
+pub struct DecoderOptions {
+    max_megapixels: Option<f64>,
+    max_pixels: Option<u64>,
+    index_fallback: Option<Pixel>,
+    allow_empty: bool,
+    strict: bool,
+}
+
+impl DecoderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects images with more than `max_megapixels` million pixels when decoding the header, with
+    /// a `HeaderDecodeError::TooLarge`. This happens before any pixel data is read or any memory is
+    /// allocated for it, so it is a cheap way for servers to refuse oversized uploads.
+    pub fn max_megapixels(mut self, max_megapixels: f64) -> Self {
+        self.max_megapixels = Some(max_megapixels);
+        self
+    }
+
+    /// Like `max_megapixels`, but sets an exact limit on the number of pixels rather than a limit in
+    /// millions of pixels. If both limits are set, images must satisfy both.
+    pub fn max_pixels(mut self, max_pixels: u64) -> Self {
+        self.max_pixels = Some(max_pixels);
+        self
+    }
+
+    /// Replaces the pixels of QOI_OP_INDEX chunks which refer to an index slot that has never been
+    /// written with `fallback`, such as a bright magenta, to make corruption visible in the decoded
+    /// image. The default of `None` follows the specification, which says that unwritten slots hold
+    /// `Pixel::ZERO`.
+    ///
+    /// A pixel is only ever stored in the slot its hash refers to, and `Pixel::ZERO` hashes to slot
+    /// 0, so any other slot which holds `Pixel::ZERO` must never have been written. Slot 0 is never
+    /// replaced, because canonical encoders refer to it for a `Pixel::ZERO` pixel whether or not it
+    /// has been written. Chunks following a replaced pixel are decoded relative to the fallback.
+    pub fn index_fallback(mut self, fallback: Option<Pixel>) -> Self {
+        self.index_fallback = fallback;
+        self
+    }
+
+    /// Accepts images with a width or height of zero when decoding the header. These are allowed by
+    /// the specification but are rarely intended, so by default they are rejected with a
+    /// `HeaderDecodeError::EmptyImage`.
+    pub fn allow_empty(mut self, allow_empty: bool) -> Self {
+        self.allow_empty = allow_empty;
+        self
+    }
+
+    /// Flags QOI_OP_INDEX chunks which refer to an index slot that has never been written, which
+    /// canonical encoders never produce and which often means the stream is corrupt. This uses the
+    /// same test for unwritten slots as `index_fallback`, so slot 0 is never flagged. Such chunks are
+    /// still decoded as normal, but the whole-image methods such as `PixelDecoder::decode_pixels_vec`
+    /// and `PixelDecoder::decode_bytes_vec` then return `DecodeAllError::SuspiciousStream`. With
+    /// other methods, check `PixelDecoder::suspicious_offset` after decoding. The default is
+    /// permissive, since the specification allows these chunks.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Returns the number of pixels in an image with the given dimensions, or `None` if there are too
+    /// many pixels to count or more than these options allow.
+    fn check_dimensions(&self, width: u64, height: u64) -> Option<u64> {
+        let num_pixels = width.checked_mul(height)?;
+
+        match (self.max_megapixels, self.max_pixels) {
+            (Some(max_megapixels), _) if megapixels(width, height) > max_megapixels => None,
+            (_, Some(max_pixels)) if num_pixels > max_pixels => None,
+            _ => Some(num_pixels),
+        }
+    }
+}
+
+/// Returns the number of millions of pixels in an image with the given dimensions.
+fn megapixels(width: u64, height: u64) -> f64 {
+    width as f64 * height as f64 / 1_000_000.0
 }
 
 impl<'a> Decoder<SliceByteStream<'a>> {
     pub fn new_from_slice(slice: &'a [u8]) -> Self {
         Self::new(slice.into())
     }
+
+    /// Decodes the header without consuming the decoder, so that `decode_header` can still be called
+    /// afterwards. The decoder's options are applied, so this fails in the same cases as
+    /// `decode_header` does.
+    pub fn peek_header(&self) -> Result<Header, HeaderDecodeError<convert::Infallible>> {
+        Decoder {
+            stream: self.stream.clone(),
+            options: self.options.clone(),
+        }
+        .decode_header()
+        .map(|(header, _)| header)
+    }
 }
 
 impl<I, T> Decoder<IterByteStream<I>>
@@ -37,6 +186,7 @@ where
     }
 }
 
+#[cfg(feature = "std")]
 impl<R> Decoder<ReadByteStream<R>>
 where
     R: io::Read,
@@ -46,12 +196,143 @@ where
     }
 }
 
+#[cfg(feature = "std")]
+impl<'a> Decoder<ReadByteStream<&'a mut dyn io::Read>> {
+    /// Like `new_from_reader`, but reads through a trait object. The decoder is then compiled once
+    /// for all reader types rather than once for each, at the cost of a dynamic call for every read,
+    /// which suits plugins and other code handling many kinds of reader.
+    pub fn new_from_dyn_reader(reader: &'a mut dyn io::Read) -> Self {
+        Self::new(reader.into())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R> Decoder<ReadByteStream<R>>
+where
+    R: io::BufRead,
+{
+    /// Decodes the header only if the stream starts with the QOI magic bytes, which are peeked from
+    /// the reader's buffer without being consumed. If they do not match, the decoder is returned as
+    /// the outer error with its stream untouched, so that the same reader can be passed to another
+    /// format's decoder. This allows formats to be sniffed from a non-seekable source such as a
+    /// pipe. Once the magic bytes match, this behaves like `decode_header`.
+    ///
+    /// If the reader's buffer holds fewer than 4 bytes, only those bytes can be compared with the
+    /// magic bytes. If they match the start of the magic bytes, the header is decoded as normal, so
+    /// a stream which only diverges from the magic bytes after that point will have been consumed.
+    #[allow(clippy::type_complexity)]
+    pub fn try_decode_header(
+        mut self,
+    ) -> Result<
+        Result<(Header, PixelDecoder<ReadByteStream<R>>), HeaderDecodeError<Box<io::Error>>>,
+        Self,
+    > {
+        match starts_with_magic(self.stream.inner_mut()) {
+            Ok(true) => Ok(self.decode_header()),
+            Ok(false) => Err(self),
+            Err(err) => Ok(Err(HeaderDecodeError::Io(Box::new(err)))),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R> Decoder<BufReadByteStream<R>>
+where
+    R: io::BufRead,
+{
+    /// Creates a decoder which reads from the reader's buffer directly. This is faster than
+    /// `new_from_reader` for readers which are already buffered, such as an `io::BufReader`.
+    pub fn new_from_buf_reader(reader: R) -> Self {
+        Self::new(reader.into())
+    }
+
+    /// Decodes the header only if the stream starts with the QOI magic bytes, which are peeked from
+    /// the reader's buffer without being consumed; see `Decoder::<ReadByteStream<R>>::try_decode_header`.
+    #[allow(clippy::type_complexity)]
+    pub fn try_decode_header(
+        mut self,
+    ) -> Result<
+        Result<(Header, PixelDecoder<BufReadByteStream<R>>), HeaderDecodeError<Box<io::Error>>>,
+        Self,
+    > {
+        match starts_with_magic(self.stream.inner_mut()) {
+            Ok(true) => Ok(self.decode_header()),
+            Ok(false) => Err(self),
+            Err(err) => Ok(Err(HeaderDecodeError::Io(Box::new(err)))),
+        }
+    }
+}
+
+#[cfg(all(feature = "tokio", feature = "std"))]
+impl<R> Decoder<AsyncByteStream<R>>
+where
+    R: AsyncRead + Unpin,
+{
+    pub fn new_from_async_reader(reader: R) -> Self {
+        Self::new(reader.into())
+    }
+
+    /// Waits for the header to be read from the reader, then decodes it as `decode_header` does.
+    pub async fn decode_header_async(
+        mut self,
+    ) -> Result<(Header, PixelDecoder<AsyncByteStream<R>>), HeaderDecodeError<Box<io::Error>>> {
+        // If the reader ends early, `decode_header` reports it
+        self.stream
+            .fill_to(HEADER_LEN)
+            .await
+            .map_err(|err| HeaderDecodeError::Io(Box::new(err)))?;
+
+        self.decode_header()
+    }
+}
+
+/// Returns false if the bytes in the reader's buffer do not match the start of the QOI magic bytes,
+/// without consuming them. Returns true if they match or if the buffer is empty, in which case
+/// decoding the header will report the end of the stream.
+#[cfg(feature = "std")]
+fn starts_with_magic<R>(reader: &mut R) -> io::Result<bool>
+where
+    R: io::BufRead,
+{
+    let buf = reader.fill_buf()?;
+    let len = buf.len().min(Header::MAGIC.len());
+    Ok(buf[..len] == Header::MAGIC[..len])
+}
+
 impl<S> Decoder<S>
 where
     S: ByteStream,
 {
     pub fn new(stream: S) -> Self {
-        Self { stream }
+        Self {
+            stream,
+            options: DecoderOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: DecoderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Rejects images with more than `max_pixels` pixels when decoding the header, with a
+    /// `HeaderDecodeError::TooLarge`. This is a shorthand for setting `DecoderOptions::max_pixels`,
+    /// and keeps the decoder's other options.
+    pub fn with_pixel_limit(mut self, max_pixels: u64) -> Self {
+        self.options.max_pixels = Some(max_pixels);
+        self
+    }
+
+    /// Enables strict decoding, which is a shorthand for setting `DecoderOptions::strict`, and keeps
+    /// the decoder's other options.
+    pub fn strict(mut self) -> Self {
+        self.options.strict = true;
+        self
+    }
+
+    /// Returns the underlying stream, which will not have been read from.
+    pub fn into_stream(self) -> S {
+        self.stream
     }
 
     pub fn decode_header(
@@ -64,122 +345,1328 @@ where
         let channels = self.stream.read_one()?.try_into()?;
         let col_space = self.stream.read_one()?.try_into()?;
 
-        let num_pixels = width as u64 * height as u64;
+        let num_pixels = self.options.check_dimensions(width.into(), height.into())
+            .ok_or_else(|| HeaderDecodeError::TooLarge {
+                megapixels: megapixels(width.into(), height.into()),
+            })?;
+
+        if num_pixels == 0 && !self.options.allow_empty {
+            return Err(HeaderDecodeError::EmptyImage);
+        }
+
+        Ok((
+            Header::new(width, height, channels, col_space),
+            PixelDecoder::new(self.stream, num_pixels, &self.options),
+        ))
+    }
+
+    /// Checks the whole file against the QOI specification, without storing any of its pixels, and
+    /// returns a report of every problem found rather than stopping at the first one. This checks the
+    /// header fields, the size limit set in the decoder's options, that the pixel data is exactly as
+    /// long as the header says, and that it is followed by the end marker and nothing else.
+    ///
+    /// If `check_canonical` is true, this also checks that every chunk is the one a canonical encoder
+    /// would have chosen: for example, that repeated pixels are always encoded as runs, and that no
+    /// QOI_OP_RGBA chunk is used when the alpha has not changed. Files which fail this check are still
+    /// valid QOI, but suggest an unusual or buggy encoder.
+    ///
+    /// Problems with the file are reported rather than returned as errors, so this only fails if the
+    /// underlying stream returns an I/O error.
+    #[cfg(feature = "alloc")]
+    pub fn decode_validated(
+        mut self,
+        check_canonical: bool,
+    ) -> Result<ValidationReport, S::IoError> {
+        let mut issues = Vec::new();
+
+        let header_bytes = match until_eof(self.stream.read_n::<HEADER_LEN>())? {
+            Some(header_bytes) => header_bytes,
+            None => {
+                issues.push(ValidationIssue::TruncatedHeader);
+                return Ok(ValidationReport::new(None, issues));
+            }
+        };
+
+        let [m0, m1, m2, m3, w0, w1, w2, w3, h0, h1, h2, h3, channels, col_space] = header_bytes;
+
+        let width = u32::from_be_bytes([w0, w1, w2, w3]);
+        let height = u32::from_be_bytes([h0, h1, h2, h3]);
+
+        let magic = Header::validate_magic([m0, m1, m2, m3])
+            .map_err(|err| issues.push(ValidationIssue::Magic(err)));
+
+        let channels = Channels::try_from(channels)
+            .map_err(|err| issues.push(ValidationIssue::Channels(err)));
+
+        let col_space = ColSpace::try_from(col_space)
+            .map_err(|err| issues.push(ValidationIssue::ColSpace(err)));
+
+        let header = match (magic, channels, col_space) {
+            (Ok(()), Ok(channels), Ok(col_space)) => {
+                Some(Header::new(width, height, channels, col_space))
+            }
+            _ => None,
+        };
+
+        let num_pixels = match self.options.check_dimensions(width.into(), height.into()) {
+            Some(num_pixels) => num_pixels,
+            None => {
+                issues.push(ValidationIssue::TooLarge {
+                    megapixels: megapixels(width.into(), height.into()),
+                });
+                return Ok(ValidationReport::new(header, issues));
+            }
+        };
+
+        // Validation checks the file against the specification, so the fallback is not used
+        PixelDecoder::new(self.stream, num_pixels, &DecoderOptions::default())
+            .validate_chunks(HEADER_LEN as u64, check_canonical, &mut issues)?;
+
+        Ok(ValidationReport::new(header, issues))
+    }
+
+    /// Decodes either a standard QOI header or a header using the non-standard 64-bit dimension
+    /// extension described in the `ext64` module, depending on the magic bytes. Returns a
+    /// `HeaderDecodeError::TooLarge` if the total number of pixels does not fit in a `u64`, or if it
+    /// exceeds the limit set in the decoder's options.
+    #[cfg(feature = "ext64")]
+    pub fn decode_header_ext64(
+        mut self,
+    ) -> Result<(Header64, PixelDecoder<S>), HeaderDecodeError<S::IoError>> {
+        let magic = self.stream.read_n()?;
+
+        let (width, height) = if magic == Header64::MAGIC {
+            (
+                u64::from_be_bytes(self.stream.read_n()?),
+                u64::from_be_bytes(self.stream.read_n()?),
+            )
+        } else {
+            Header::validate_magic(magic)?;
+            (
+                u32::from_be_bytes(self.stream.read_n()?).into(),
+                u32::from_be_bytes(self.stream.read_n()?).into(),
+            )
+        };
+
+        let channels = self.stream.read_one()?.try_into()?;
+        let col_space = self.stream.read_one()?.try_into()?;
+
+        let num_pixels = self.options.check_dimensions(width, height)
+            .ok_or_else(|| HeaderDecodeError::TooLarge {
+                megapixels: megapixels(width, height),
+            })?;
+
+        if num_pixels == 0 && !self.options.allow_empty {
+            return Err(HeaderDecodeError::EmptyImage);
+        }
+
+        Ok((
+            Header64::new(width, height, channels, col_space),
+            PixelDecoder::new(self.stream, num_pixels, &self.options),
+        ))
+    }
+
+    /// Decodes the header of the non-standard variant with an `N`-slot index described in the
+    /// `wide_index` module. Returns a `HeaderDecodeError::NotWide` if the magic bytes are not those
+    /// of the variant, or a `HeaderDecodeError::IndexSize` if the file was written with a different
+    /// index size. The decoder's options other than the size limit and `allow_empty` are ignored.
+    #[cfg(feature = "wide_index")]
+    pub fn decode_header_wide<const N: usize>(
+        mut self,
+    ) -> Result<(Header, WidePixelDecoder<S, N>), HeaderDecodeError<S::IoError>> {
+        let magic = self.stream.read_n()?;
+        if magic != wide_index::MAGIC {
+            return Err(HeaderDecodeError::NotWide { magic });
+        }
+
+        let width = u32::from_be_bytes(self.stream.read_n()?);
+        let height = u32::from_be_bytes(self.stream.read_n()?);
+        let channels = self.stream.read_one()?.try_into()?;
+        let col_space = self.stream.read_one()?.try_into()?;
+
+        let index_bits = self.stream.read_one()?;
+        if index_bits != IndexBits::<N>::VALUE {
+            return Err(HeaderDecodeError::IndexSize {
+                expected: IndexBits::<N>::VALUE,
+                found: index_bits,
+            });
+        }
+
+        let num_pixels = self.options.check_dimensions(width.into(), height.into())
+            .ok_or_else(|| HeaderDecodeError::TooLarge {
+                megapixels: megapixels(width.into(), height.into()),
+            })?;
+
+        if num_pixels == 0 && !self.options.allow_empty {
+            return Err(HeaderDecodeError::EmptyImage);
+        }
 
         Ok((
             Header::new(width, height, channels, col_space),
-            PixelDecoder::new(self.stream, num_pixels),
+            WidePixelDecoder::new(self.stream, num_pixels),
         ))
     }
 }
 
+/// Parses a QOI header from the given bytes, and returns it along with an iterator which lazily
+/// decodes the image's pixels from the rest of the bytes. The bytes may be either `u8`s or
+/// `Result<u8, E>`s, in which case any errors are passed through to the returned iterator.
+#[allow(clippy::type_complexity)]
+pub fn pixels<I, T>(
+    bytes: I,
+) -> Result<(Header, Pixels<IterByteStream<I::IntoIter>>), HeaderDecodeError<T::IoError>>
+where
+    I: IntoIterator<Item = T>,
+    T: IntoStreamResult,
+{
+    Decoder::new_from_iter(bytes)
+        .decode_header()
+        .map(|(header, decoder)| (header, Pixels::new(decoder)))
+}
+
 pub struct PixelDecoder<S> {
     stream: S,
     previous: Pixel,
-    index: PixelIndex,
+    index: QoiIndex,
     remaining: u64,
     run: u8,
+    /// The tag byte of a chunk which could not be decoded because the stream ended partway through
+    /// it, which is decoded first the next time a chunk is needed.
+    pending_tag: Option<u8>,
+    index_fallback: Option<Pixel>,
+    strict: bool,
+    /// In strict mode, whether a QOI_OP_INDEX chunk has referred to an unwritten index slot.
+    suspicious: bool,
+    /// The offset of the first such chunk, if the stream counts the bytes it has consumed.
+    suspicious_offset: Option<u64>,
+    /// The stream's `bytes_consumed` when the chunk currently being decoded was started, used to
+    /// check that no chunk consumes more than `MAX_CHUNK_LEN` bytes.
+    #[cfg(debug_assertions)]
+    chunk_start: Option<u64>,
+}
+
+/// A snapshot of a `PixelDecoder`'s position in an image, which does not include the stream. See
+/// `PixelDecoder::state`.
+#[derive(Clone)]
+pub struct DecoderState {
+    previous: Pixel,
+    index: QoiIndex,
+    remaining: u64,
+    run: u8,
+    pending_tag: Option<u8>,
+}
+
+impl DecoderState {
+    /// Returns the number of pixels which were still to be decoded when the snapshot was taken.
+    pub fn remaining_pixels(&self) -> u64 {
+        self.remaining
+    }
+}
+
+impl<S> PixelDecoder<S> {
+    /// Returns a snapshot of the decoder's state: the previous pixel, the index, the number of
+    /// pixels remaining and any run in progress. Along with the number of bytes consumed from the
+    /// stream, this is everything needed to resume decoding later with `restore_state`.
+    pub fn state(&self) -> DecoderState {
+        DecoderState {
+            previous: self.previous,
+            index: self.index.clone(),
+            remaining: self.remaining,
+            run: self.run,
+            pending_tag: self.pending_tag,
+        }
+    }
+
+    /// Replaces the decoder's state with a snapshot taken by `state`. The decoder's stream must be
+    /// positioned at the same byte offset in the same image as the stream of the decoder the
+    /// snapshot was taken from, otherwise the pixels decoded afterwards will be garbage.
+    pub fn restore_state(&mut self, state: DecoderState) {
+        self.previous = state.previous;
+        self.index = state.index;
+        self.remaining = state.remaining;
+        self.run = state.run;
+        self.pending_tag = state.pending_tag;
+    }
+
+    /// Returns a mutable reference to the underlying stream. Reading from it moves the decoder's
+    /// position in the image, so this is best left until every pixel has been decoded, after which
+    /// the stream is positioned at the end marker.
+    pub fn stream_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns the underlying stream, for example to read the end marker or any data after the
+    /// image. If decoding stopped because the stream ended partway through a chunk, the tag byte of
+    /// that chunk has already been taken from the stream.
+    pub fn into_stream(self) -> S {
+        self.stream
+    }
+
+    /// Moves the decoder's state onto a different stream, so that decoding can continue from the
+    /// same position in the image.
+    #[cfg(feature = "alloc")]
+    fn with_stream<T>(self, stream: T) -> PixelDecoder<T> {
+        PixelDecoder {
+            stream,
+            previous: self.previous,
+            index: self.index,
+            remaining: self.remaining,
+            run: self.run,
+            pending_tag: self.pending_tag,
+            index_fallback: self.index_fallback,
+            strict: self.strict,
+            suspicious: self.suspicious,
+            suspicious_offset: self.suspicious_offset,
+            #[cfg(debug_assertions)]
+            chunk_start: self.chunk_start,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> PixelDecoder<SliceByteStream<'a>> {
+    /// Decodes pixels into the given buffer until the buffer becomes full, the end of the image is
+    /// reached, or the next chunk is not entirely contained in the slice. Unlike the other decoding
+    /// methods, running out of bytes is not an error, and never leaves a chunk partially consumed.
+    /// Returns the number of pixels written to the buffer.
+    fn decode_complete_chunks_into(&mut self, buf: &mut [Pixel]) -> usize {
+        // Only whole chunks in the slice are decoded, so this cannot fail
+        let decoded = self.decode_pixels_into_partial(buf);
+        debug_assert!(decoded.is_ok());
+        decoded.map_or(0, |(num_pixels, _)| num_pixels)
+    }
+}
+
+impl<S> PixelDecoder<S>
+where
+    S: BufferedByteStream,
+{
+    /// Decodes pixels into the given buffer until the buffer becomes full, the end of the image is
+    /// reached, or the next chunk has not been entirely buffered by the stream yet. Returns the
+    /// number of pixels written to the buffer and the reason decoding stopped.
+    ///
+    /// A chunk is only read once all of its bytes are available, so running out of bytes never
+    /// leaves a chunk partially consumed. When this returns `PartialDecode::Pending`, the decoder can
+    /// be called again once more bytes have been added to the stream, for example as they arrive
+    /// from a socket.
+    pub fn decode_pixels_into_partial(
+        &mut self,
+        buf: &mut [Pixel],
+    ) -> Result<(usize, PartialDecode), StreamError<S::IoError>> {
+        let max_pixels = usize::try_from(self.remaining)
+            .map_or(buf.len(), |remaining| remaining.min(buf.len()));
+
+        let mut num_pixels = 0;
+
+        for dst in &mut buf[..max_pixels] {
+            if self.run > 0 {
+                self.run -= 1;
+            } else {
+                let ready = match self.pending_tag {
+                    // The tag has already been consumed, so only the rest of the chunk is needed
+                    Some(b0) => self.stream.buffered_len() >= chunk_len(b0) - 1,
+                    None => match self.stream.peek_one()? {
+                        Some(b0) => self.stream.buffered_len() >= chunk_len(b0),
+                        None => false,
+                    },
+                };
+
+                if !ready {
+                    break;
+                }
+
+                self.decode_next_chunk()?;
+            }
+
+            *dst = self.previous;
+            num_pixels += 1;
+        }
+
+        self.remaining -= num_pixels as u64;
+
+        let status = if self.remaining == 0 {
+            PartialDecode::Finished
+        } else if num_pixels == buf.len() {
+            PartialDecode::BufferFull
+        } else {
+            PartialDecode::Pending
+        };
+
+        Ok((num_pixels, status))
+    }
 }
 
-impl<S> PixelDecoder<S>
-where
-    S: ByteStream,
-{
-    fn new(stream: S, num_pixels: u64) -> Self {
-        Self {
-            stream,
-            previous: Pixel::BLACK,
-            index: PixelIndex::new(),
-            remaining: num_pixels,
-            run: 0,
+#[cfg(all(feature = "tokio", feature = "std"))]
+impl<R> PixelDecoder<AsyncByteStream<R>>
+where
+    R: AsyncRead + Unpin,
+{
+    /// The async equivalent of `decode_pixels_into`. Whenever the stream's buffer runs out of
+    /// complete chunks, this waits for more bytes from the reader, then carries on decoding with
+    /// `decode_pixels_into_partial`, so the chunks themselves are decoded exactly as they are by
+    /// the other methods.
+    ///
+    /// Errors are returned along with the number of pixels written to `buf` before the error, so
+    /// that nothing already decoded is lost. No chunk is ever left partially consumed, so if the
+    /// reader runs out of bytes but has more to come later, for example a file which is still being
+    /// written, decoding can be resumed by calling this again with the rest of the buffer.
+    pub async fn decode_pixels_into_async(
+        &mut self,
+        buf: &mut [Pixel],
+    ) -> Result<(usize, bool), (usize, StreamError<Box<io::Error>>)> {
+        let mut num_pixels = 0;
+
+        loop {
+            let (n, status) = self.decode_pixels_into_partial(&mut buf[num_pixels..])
+                .map_err(|err| (num_pixels, err))?;
+            num_pixels += n;
+
+            match status {
+                PartialDecode::Finished => return Ok((num_pixels, true)),
+                PartialDecode::BufferFull => return Ok((num_pixels, false)),
+                PartialDecode::Pending => (),
+            }
+
+            let len = self.stream
+                .fill()
+                .await
+                .map_err(|err| (num_pixels, StreamError::Io(Box::new(err))))?;
+
+            if len == 0 {
+                return Err((num_pixels, StreamError::UnexpectedEof));
+            }
+        }
+    }
+}
+
+/// Why `PixelDecoder::decode_pixels_into_partial` stopped decoding.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PartialDecode {
+    /// Every pixel of the image has been decoded.
+    Finished,
+    /// The buffer is full, and there are more pixels to decode.
+    BufferFull,
+    /// The next chunk has not been entirely buffered yet. Decoding can continue once more bytes
+    /// are available.
+    Pending,
+}
+
+impl<S> PixelDecoder<S>
+where
+    S: ByteStream,
+{
+    fn new(stream: S, num_pixels: u64, options: &DecoderOptions) -> Self {
+        Self {
+            stream,
+            previous: Pixel::BLACK,
+            index: QoiIndex::new(),
+            remaining: num_pixels,
+            run: 0,
+            pending_tag: None,
+            index_fallback: options.index_fallback,
+            strict: options.strict,
+            suspicious: false,
+            suspicious_offset: None,
+            #[cfg(debug_assertions)]
+            chunk_start: None,
+        }
+    }
+
+    /// Decodes pixels into the given buffer until the buffer becomes full or the end of the image is
+    /// reached, whichever comes first. The returned `usize` is the number of pixels written to the
+    /// buffer. The returned `bool` is true if all of the pixels have been decoded after this operation,
+    /// and false if there are still more pixels that need to be decoded after this operation.
+    pub fn decode_pixels_into(
+        &mut self,
+        buf: &mut [Pixel],
+    ) -> Result<(usize, bool), StreamError<S::IoError>> {
+        self.decode_into_pixel_buf(buf, convert::identity)
+    }
+
+    /// Returns an `io::Read` which reads the remaining pixels as bytes, with `transform` converting
+    /// each pixel to `N` bytes. Each read decodes just enough pixels to fill the buffer it is given,
+    /// so this can be passed to `io::copy` without decoding the whole image up front.
+    #[cfg(feature = "std")]
+    pub fn into_byte_reader<F, const N: usize>(self, transform: F) -> ByteReader<S, F, N>
+    where
+        F: Fn(Pixel) -> [u8; N],
+    {
+        ByteReader::new(self, transform)
+    }
+
+    /// Decodes pixels into the given buffer until the buffer becomes full, the end of the image is
+    /// reached or the stream ends, and returns the number of pixels written to the buffer. Unlike
+    /// `decode_pixels_into`, running out of bytes is not an error, so this suits untrusted input such
+    /// as fuzzer output: memory use is bounded by the caller's buffer rather than by the header, and
+    /// whatever pixels could be decoded are returned. Errors from the underlying reader are still
+    /// returned.
+    pub fn decode_capped(&mut self, buf: &mut [Pixel]) -> Result<usize, StreamError<S::IoError>> {
+        let remaining = self.remaining;
+
+        match self.decode_into_pixel_buf(buf, convert::identity) {
+            Ok((num_pixels, _)) => Ok(num_pixels),
+            Err(StreamError::UnexpectedEof) => Ok((remaining - self.remaining) as usize),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// In strict mode, returns whether any QOI_OP_INDEX chunk decoded so far referred to an index
+    /// slot that had never been written. Always false otherwise. See `DecoderOptions::strict`.
+    pub fn is_suspicious(&self) -> bool {
+        self.suspicious
+    }
+
+    /// In strict mode, returns the offset in the file of the first QOI_OP_INDEX chunk decoded so far
+    /// which referred to an index slot that had never been written. Always `None` otherwise, or if
+    /// the stream does not count the bytes it consumes. See `DecoderOptions::strict`.
+    pub fn suspicious_offset(&self) -> Option<u64> {
+        self.suspicious_offset
+    }
+
+    /// Returns the number of bytes read from the stream so far, including the header, or `None` if
+    /// the stream does not count them. Once every pixel has been decoded, this is the offset of the
+    /// end marker.
+    pub fn bytes_consumed(&self) -> Option<u64> {
+        self.stream.bytes_consumed()
+    }
+
+    /// Decodes a single pixel, or returns `None` if every pixel of the image has already been decoded.
+    /// This is useful for driving the decoder one pixel at a time, but decoding into a buffer with
+    /// `decode_pixels_into` is faster when many pixels are needed.
+    #[inline]
+    pub fn decode_pixel(&mut self) -> Result<Option<Pixel>, StreamError<S::IoError>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        let pixel = self.decode_next()?;
+        self.remaining -= 1;
+        Ok(Some(pixel))
+    }
+
+    /// Decodes and discards the next `n` pixels, leaving the decoder positioned to decode the pixel
+    /// after them, and returns the number of pixels skipped, which is only less than `n` if the image
+    /// has fewer pixels left. QOI has no way of seeking, so this still has to decode every chunk, but
+    /// runs are skipped in one step and nothing is written to a buffer.
+    pub fn skip_pixels(&mut self, n: u64) -> Result<u64, StreamError<S::IoError>> {
+        let num_pixels = n.min(self.remaining);
+        let mut left = num_pixels;
+
+        while left > 0 {
+            if self.run > 0 {
+                let skipped = u8::try_from(left).map_or(self.run, |left| left.min(self.run));
+                self.run -= skipped;
+                self.remaining -= u64::from(skipped);
+                left -= u64::from(skipped);
+            } else {
+                self.decode_next()?;
+                self.remaining -= 1;
+                left -= 1;
+            }
+        }
+
+        Ok(num_pixels)
+    }
+
+    /// Decodes the next `num_rows` rows of an image `width` pixels wide into the start of `buf`, and
+    /// returns the number of rows decoded, which is only less than `num_rows` if the image has fewer
+    /// rows left. `width` must be the width from the image's header. Panics if `buf` is too small to
+    /// hold `num_rows` rows.
+    ///
+    /// QOI can only be decoded in order, so rows are always the ones following the last pixel
+    /// decoded; to decode a band in the middle of the image, the rows above it must be decoded first.
+    pub fn decode_rows(
+        &mut self,
+        width: u32,
+        num_rows: u32,
+        buf: &mut [Pixel],
+    ) -> Result<u32, StreamError<S::IoError>> {
+        assert!(
+            buf.len() as u64 >= width as u64 * num_rows as u64,
+            "buffer too small for {} rows",
+            num_rows
+        );
+
+        let num_rows = match self.remaining.checked_div(width.into()) {
+            Some(remaining_rows) => remaining_rows.min(num_rows.into()) as u32,
+            None => 0,
+        };
+
+        // Cannot overflow, since it is no larger than the length of `buf`
+        let num_pixels = width as usize * num_rows as usize;
+        self.decode_into_pixel_buf(&mut buf[..num_pixels], convert::identity)?;
+
+        Ok(num_rows)
+    }
+
+    /// Decodes at most `max_pixels` pixels, appending them to the end of `buf`. This allows a large
+    /// image to be decoded incrementally, for example a chunk at a time between other work in an event
+    /// loop, while still collecting the whole image into a single vec. The returned `usize` is the
+    /// number of pixels appended, and the returned `bool` is true once all of the pixels have been
+    /// decoded; to decode the whole image, call this repeatedly until it returns true.
+    ///
+    /// If an error occurs, any pixels decoded before it are left at the end of `buf` and count
+    /// against the remaining pixels, so that after an `UnexpectedEof` from a stream which has more
+    /// data to come, calling this again carries on from the next pixel.
+    #[cfg(feature = "alloc")]
+    pub fn decode_pixels_budgeted(
+        &mut self,
+        buf: &mut Vec<Pixel>,
+        max_pixels: usize,
+    ) -> Result<(usize, bool), StreamError<S::IoError>> {
+        let num_pixels = usize::try_from(self.remaining)
+            .map_or(max_pixels, |remaining| remaining.min(max_pixels));
+
+        let start = buf.len();
+        let remaining = self.remaining;
+        buf.resize(start + num_pixels, Pixel::ZERO);
+
+        match self.decode_into_pixel_buf(&mut buf[start..], convert::identity) {
+            Ok((n, _)) => Ok((n, self.remaining == 0)),
+            Err(err) => {
+                // Keep the pixels which were decoded before the error, since `remaining` no longer
+                // counts them and they would otherwise be lost when decoding is resumed
+                let decoded = (remaining - self.remaining) as usize;
+                buf.truncate(start + decoded);
+                Err(err)
+            }
+        }
+    }
+
+    /// Allocates a new vec large enough for all of the remaining pixels, decodes all of the remaining
+    /// pixels into the vec, and returns the buffer. Returns a `DecodeAllError::TooLarge` if the image
+    /// has more pixels than a vec can hold, or a `DecodeAllError::Alloc` if the allocator fails to
+    /// provide enough memory.
+    #[cfg(feature = "alloc")]
+    pub fn decode_pixels_vec(mut self) -> Result<Vec<Pixel>, DecodeAllError<S::IoError>> {
+        let expected = self.remaining;
+        let num_pixels = expected.try_into().map_err(|_| DecodeAllError::TooLarge)?;
+        let buf = self.decode_pixels_vec_n(num_pixels, convert::identity)
+            .map_err(|err| self.truncated_error(err, expected))?;
+        debug_assert_eq!(self.remaining, 0);
+        Ok(buf)
+    }
+
+    /// Like `decode_pixels_vec`, but also reads the 8 bytes following the last pixel and checks that
+    /// they are the end marker, returning a `DecodeAllError::BadEndMarker` if they are not. This
+    /// catches some truncated or corrupt files which `decode_pixels_vec` would accept, at the cost of
+    /// reading 8 more bytes. Any bytes after the end marker are not read.
+    #[cfg(feature = "alloc")]
+    pub fn decode_pixels_vec_checked(mut self) -> Result<Vec<Pixel>, DecodeAllError<S::IoError>> {
+        let expected = self.remaining;
+        let num_pixels = expected.try_into().map_err(|_| DecodeAllError::TooLarge)?;
+        let buf = self.decode_pixels_vec_n(num_pixels, convert::identity)
+            .map_err(|err| self.truncated_error(err, expected))?;
+        debug_assert_eq!(self.remaining, 0);
+
+        let end_marker = self.stream.read_n::<8>()?;
+        if end_marker != Header::END_MARKER {
+            return Err(DecodeAllError::BadEndMarker { found: end_marker });
+        }
+
+        Ok(buf)
+    }
+
+    /// Like `decode_pixels_vec`, but converts each pixel from the color space declared in the given
+    /// header to `target` as it is decoded. If the header's color space is already `target`, the
+    /// pixels are returned unchanged. Alpha is never modified.
+    ///
+    /// The converted pixels are rounded back to 8 bits per channel, so converting loses precision;
+    /// see `Pixel::srgb_to_linear` and `Pixel::linear_to_srgb`.
+    #[cfg(feature = "alloc")]
+    pub fn decode_converting(
+        mut self,
+        header: &Header,
+        target: ColSpace,
+    ) -> Result<Vec<Pixel>, DecodeAllError<S::IoError>> {
+        let num_pixels = self.remaining.try_into().map_err(|_| DecodeAllError::TooLarge)?;
+
+        let transform = match (header.col_space(), target) {
+            (ColSpace::Srgb, ColSpace::Linear) => Pixel::srgb_to_linear,
+            (ColSpace::Linear, ColSpace::Srgb) => Pixel::linear_to_srgb,
+            _ => convert::identity,
+        };
+
+        self.decode_pixels_vec_n(num_pixels, transform)
+    }
+
+    /// Decodes all of the remaining pixels into a new `Arc<[Pixel]>`, for sharing the decoded image
+    /// between threads. The pixels are decoded directly into the `Arc`'s allocation, which avoids the
+    /// copy (and the second buffer) that `Arc::from(decoder.decode_pixels_vec()?)` would need.
+    ///
+    /// Returns a `DecodeAllError::TooLarge` if the image is too large to fit in memory at all, but
+    /// unlike `decode_pixels_vec`, a failure of the allocator itself aborts rather than returning an
+    /// error, since `Arc` offers no fallible way to allocate.
+    #[cfg(feature = "alloc")]
+    pub fn decode_pixels_arc(mut self) -> Result<Arc<[Pixel]>, DecodeAllError<S::IoError>> {
+        let num_pixels = self.remaining.try_into().map_err(|_| DecodeAllError::TooLarge)?;
+
+        check_alloc_len::<Pixel, _>(num_pixels)?;
+
+        let mut buf: Arc<[Pixel]> = iter::repeat(Pixel::ZERO).take(num_pixels).collect();
+
+        // We hold the only reference to the newly-created `Arc`, so `get_mut` cannot fail
+        let dst = Arc::get_mut(&mut buf).unwrap();
+        let (_, exhausted) = self.decode_into_pixel_buf(dst, convert::identity)?;
+
+        debug_assert!(exhausted);
+
+        self.check_suspicious()?;
+        Ok(buf)
+    }
+
+    /// Decodes the first `n` rows of the image into a new vec, and returns the vec along with the
+    /// decoder so that the rest of the image can be decoded later. `n` is clamped to the height of the
+    /// image. The given header must be the one returned alongside this decoder by `decode_header`,
+    /// and no pixels may have been decoded yet; panics otherwise.
+    #[cfg(feature = "alloc")]
+    pub fn decode_first_rows(
+        mut self,
+        header: &Header,
+        n: u32,
+    ) -> Result<(Vec<Pixel>, Self), DecodeAllError<S::IoError>> {
+        self.assert_matches_header(header);
+
+        let (width, height) = header.dimensions();
+
+        let num_pixels = (width as u64 * n.min(height) as u64)
+            .try_into()
+            .map_err(|_| DecodeAllError::TooLarge)?;
+
+        let buf = self.decode_pixels_vec_n(num_pixels, convert::identity)?;
+        Ok((buf, self))
+    }
+
+    /// Like `decode_pixels_vec`, but stores the rows of the image in reverse order so that the bottom
+    /// row comes first. This is the layout expected by OpenGL texture uploads and BMP files. The given
+    /// header must be the one returned alongside this decoder by `decode_header`, and no pixels may
+    /// have been decoded yet; panics otherwise.
+    ///
+    /// Each row is decoded directly into its final position in the output vec, so this uses the same
+    /// amount of memory as `decode_pixels_vec` and no additional buffering is needed.
+    #[cfg(feature = "alloc")]
+    pub fn decode_pixels_vec_flipped(
+        mut self,
+        header: &Header,
+    ) -> Result<Vec<Pixel>, DecodeAllError<S::IoError>> {
+        self.assert_matches_header(header);
+
+        let (width, height) = header.dimensions();
+
+        let num_pixels = self.remaining.try_into().map_err(|_| DecodeAllError::TooLarge)?;
+
+        let width = width as usize;
+
+        let buf = decode_into_new_vec(num_pixels, Pixel::ZERO, |dst| {
+            for row in (0..height as usize).rev() {
+                let row_start = row * width;
+                let row_buf = &mut dst[row_start..(row_start + width)];
+                self.decode_into_pixel_buf(row_buf, convert::identity)?;
+            }
+            Ok(num_pixels)
+        })?;
+
+        self.check_suspicious()?;
+        Ok(buf)
+    }
+
+    pub fn decode_bytes_into<F, const N: usize>(
+        &mut self,
+        buf: &mut [u8],
+        transform: F,
+    ) -> Result<(usize, bool), StreamError<S::IoError>>
+    where
+        F: Fn(Pixel) -> [u8; N],
+    {
+        assert!(N != 0);
+
+        let num_pixels = buf.len() / N;
+
+        #[cfg(not(feature = "safe_only"))]
+        {
+            let ptr = buf.as_mut_ptr() as *mut [u8; N];
+            let dst = unsafe { slice::from_raw_parts_mut(ptr, num_pixels) };
+
+            self.decode_into_pixel_buf(dst, transform)
+                .map(|(n, exhausted)| (n * N, exhausted))
+        }
+
+        // Without unsafe we cannot view the byte buffer as a slice of `[u8; N]`, so decode the pixels
+        // in batches into an array on the stack and copy each batch into the buffer
+        #[cfg(feature = "safe_only")]
+        {
+            const BATCH_LEN: usize = 64;
+
+            let mut batch = [[0; N]; BATCH_LEN];
+            let mut num_decoded = 0;
+
+            for dst in buf[..(num_pixels * N)].chunks_mut(N * BATCH_LEN) {
+                let batch_len = dst.len() / N;
+                let (n, exhausted) = self.decode_into_pixel_buf(&mut batch[..batch_len], &transform)?;
+
+                for (dst_pixel, pixel) in dst.chunks_exact_mut(N).zip(&batch[..n]) {
+                    dst_pixel.copy_from_slice(pixel);
+                }
+
+                num_decoded += n;
+
+                if exhausted {
+                    return Ok((num_decoded * N, true));
+                }
+            }
+
+            Ok((num_decoded * N, self.remaining == 0))
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn decode_bytes_vec<F, const N: usize>(
+        mut self,
+        transform: F,
+    ) -> Result<Vec<u8>, DecodeAllError<S::IoError>>
+    where
+        F: Fn(Pixel) -> [u8; N],
+    {
+        assert!(N != 0);
+
+        let expected = self.remaining;
+
+        let num_pixels = usize::try_from(expected)
+            .map_err(|_| DecodeAllError::TooLarge)?;
+
+        let num_bytes = num_pixels.checked_mul(N)
+            .ok_or(DecodeAllError::TooLarge)?;
+
+        let buf = decode_into_new_vec(num_bytes, 0, |dst| {
+            let (n, exhausted) = self.decode_bytes_into(dst, transform)?;
+            debug_assert!(exhausted);
+            Ok(n)
+        })
+        .map_err(|err| self.truncated_error(err, expected))?;
+
+        self.check_suspicious()?;
+        Ok(buf)
+    }
+
+    /// Decodes all of the remaining pixels into a vec of bytes with as many channels as the given
+    /// header declares: RGB for `Channels::Rgb` and RGBA for `Channels::Rgba`. Returns the bytes along
+    /// with the number of bytes per pixel, 3 or 4, so that callers do not need to work out the stride
+    /// themselves.
+    #[cfg(feature = "alloc")]
+    pub fn decode_native_bytes_vec(
+        self,
+        header: &Header,
+    ) -> Result<(Vec<u8>, usize), DecodeAllError<S::IoError>> {
+        match header.channels() {
+            Channels::Rgb => self.decode_bytes_vec(Pixel::rgb).map(|buf| (buf, 3)),
+            Channels::Rgba => self.decode_bytes_vec(Pixel::rgba).map(|buf| (buf, 4)),
+        }
+    }
+
+    /// Like `decode_bytes_vec`, but decodes into a buffer whose start is aligned to `align` bytes, so
+    /// it can be handed directly to graphics APIs which require aligned staging buffers without
+    /// copying. `align` must be a power of two; panics otherwise. The buffer is zero-filled before
+    /// decoding, so this is slightly slower than `decode_bytes_vec`.
+    #[cfg(feature = "aligned")]
+    pub fn decode_bytes_aligned<F, const N: usize>(
+        mut self,
+        align: usize,
+        transform: F,
+    ) -> Result<AlignedBuf, DecodeAllError<S::IoError>>
+    where
+        F: Fn(Pixel) -> [u8; N],
+    {
+        assert!(N != 0);
+
+        let num_pixels = usize::try_from(self.remaining)
+            .map_err(|_| DecodeAllError::TooLarge)?;
+
+        let num_bytes = num_pixels.checked_mul(N)
+            .ok_or(DecodeAllError::TooLarge)?;
+
+        let mut buf = AlignedBuf::zeroed(num_bytes, align)
+            .map_err(DecodeAllError::Alloc)?;
+
+        let (n, exhausted) = self.decode_bytes_into(&mut buf, transform)?;
+        debug_assert!(exhausted);
+        buf.truncate(n);
+
+        Ok(buf)
+    }
+
+    /// Decodes the rest of the image into a vec of packed RGB bytes, deliberately discarding the alpha
+    /// channel. This is the same as `decode_bytes_vec(Pixel::rgb)`, except that it also returns
+    /// whether every pixel was fully opaque. If it returns `false`, some of the discarded alpha values
+    /// were not 255, so information was lost; callers can use this to warn about or reject images
+    /// whose transparency would be silently flattened.
+    #[cfg(feature = "alloc")]
+    pub fn decode_rgb_vec_dropping_alpha(
+        self,
+    ) -> Result<(Vec<u8>, bool), DecodeAllError<S::IoError>> {
+        let all_opaque = Cell::new(true);
+
+        let buf = self.decode_bytes_vec(|pixel| {
+            all_opaque.set(all_opaque.get() & (pixel.a == u8::MAX));
+            pixel.rgb()
+        })?;
+
+        Ok((buf, all_opaque.get()))
+    }
+
+    /// Decodes the rest of the image and returns the mean of each of its channels, rounded to the
+    /// nearest integer, which is useful as a single-color placeholder while the full image loads.
+    /// The pixels are decoded into a small fixed-size buffer which is reused, so memory use does not
+    /// depend on the size of the image. Returns `Pixel::ZERO` for an image with no pixels.
+    ///
+    /// Fully transparent pixels often have arbitrary colors; see
+    /// `decode_average_color_alpha_weighted` for an average which ignores them.
+    pub fn decode_average_color(mut self) -> Result<Pixel, DecodeAllError<S::IoError>> {
+        self.decode_average_color_impl(false)
+    }
+
+    /// Like `decode_average_color`, but weights the red, green and blue channels of each pixel by its
+    /// alpha, so more transparent pixels contribute less to the average color. The alpha channel of
+    /// the result is the unweighted mean alpha. If every pixel is fully transparent, the red, green
+    /// and blue channels of the result are zero.
+    pub fn decode_average_color_alpha_weighted(
+        mut self,
+    ) -> Result<Pixel, DecodeAllError<S::IoError>> {
+        self.decode_average_color_impl(true)
+    }
+
+    fn decode_average_color_impl(
+        &mut self,
+        alpha_weighted: bool,
+    ) -> Result<Pixel, DecodeAllError<S::IoError>> {
+        const BATCH_LEN: usize = 256;
+
+        let mut batch = [Pixel::ZERO; BATCH_LEN];
+
+        // Sums of each channel, and the total weight of the red, green and blue sums. A `u128` cannot
+        // overflow, even with 2^64 pixels weighted by 255 * 255
+        let mut sums = [0u128; 4];
+        let mut weight = 0u128;
+        let mut num_pixels = 0u128;
+
+        loop {
+            let (n, exhausted) = self.decode_into_pixel_buf(&mut batch, convert::identity)?;
+
+            for pixel in &batch[..n] {
+                let w = if alpha_weighted { pixel.a as u128 } else { 1 };
+                sums[0] += pixel.r as u128 * w;
+                sums[1] += pixel.g as u128 * w;
+                sums[2] += pixel.b as u128 * w;
+                sums[3] += pixel.a as u128;
+                weight += w;
+            }
+
+            num_pixels += n as u128;
+
+            if exhausted {
+                break;
+            }
+        }
+
+        let mean = |sum: u128, count: u128| match count {
+            0 => 0,
+            // The mean of `u8`s always fits in a `u8`
+            _ => ((sum + count / 2) / count) as u8,
+        };
+
+        Ok(Pixel::new(
+            mean(sums[0], weight),
+            mean(sums[1], weight),
+            mean(sums[2], weight),
+            mean(sums[3], num_pixels),
+        ))
+    }
+
+    /// Decodes the image one row at a time, calling `f` with the index of each row and its pixels
+    /// once the row has been fully decoded. Only a single row of pixels is ever held in memory, which
+    /// is reused for every row. The given header must be the one returned alongside this decoder by
+    /// `decode_header`, and no pixels may have been decoded yet; panics otherwise.
+    #[cfg(feature = "alloc")]
+    pub fn decode_by_row<F>(
+        mut self,
+        header: &Header,
+        mut f: F,
+    ) -> Result<(), DecodeAllError<S::IoError>>
+    where
+        F: FnMut(u32, &[Pixel]),
+    {
+        self.assert_matches_header(header);
+
+        let (width, height) = header.dimensions();
+
+        let width = width.try_into().map_err(|_| DecodeAllError::TooLarge)?;
+        let mut row = try_alloc_vec(width)?;
+        row.resize(width, Pixel::ZERO);
+
+        for y in 0..height {
+            self.decode_into_pixel_buf(&mut row, convert::identity)?;
+            f(y, &row);
+        }
+
+        self.check_suspicious()
+    }
+
+    /// Decodes the rest of the image into a vec, and also returns the bounding box `(x, y, w, h)` of
+    /// all of its pixels with non-zero alpha, or `None` if every pixel is fully transparent. This is
+    /// the region a sprite packer would crop the image to. For an image with no transparent pixels
+    /// (including any RGB image), the bounding box is the whole image. The given header must be the
+    /// one returned alongside this decoder by `decode_header`, and no pixels may have been decoded
+    /// yet; panics otherwise.
+    ///
+    /// The bounds are updated as each row is decoded, while the row is still in cache, rather than in
+    /// a second pass over the whole image.
+    #[allow(clippy::type_complexity)]
+    #[cfg(feature = "alloc")]
+    pub fn decode_with_alpha_bounds(
+        mut self,
+        header: &Header,
+    ) -> Result<(Vec<Pixel>, Option<(u32, u32, u32, u32)>), DecodeAllError<S::IoError>> {
+        self.assert_matches_header(header);
+
+        let num_pixels = self.remaining.try_into().map_err(|_| DecodeAllError::TooLarge)?;
+
+        let width = header.width() as usize;
+
+        // (min x, min y, max x, max y), all inclusive
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+
+        let buf = decode_into_new_vec(num_pixels, Pixel::ZERO, |dst| {
+            if width == 0 {
+                return Ok(0);
+            }
+
+            for (y, row) in dst.chunks_exact_mut(width).enumerate() {
+                self.decode_into_pixel_buf(row, convert::identity)?;
+
+                let min_x = match row.iter().position(|pixel| pixel.a != 0) {
+                    Some(min_x) => min_x,
+                    None => continue,
+                };
+
+                // There is at least one visible pixel in the row, so this always finds one
+                let max_x = row.iter().rposition(|pixel| pixel.a != 0).unwrap_or(min_x);
+
+                bounds = Some(match bounds {
+                    Some((x0, y0, x1, _)) => (x0.min(min_x), y0, x1.max(max_x), y),
+                    None => (min_x, y, max_x, y),
+                });
+            }
+
+            Ok(num_pixels)
+        })?;
+
+        // The bounds are within the image, whose dimensions are u32s
+        let bounds = bounds.map(|(x0, y0, x1, y1)| {
+            (x0 as u32, y0 as u32, (x1 - x0 + 1) as u32, (y1 - y0 + 1) as u32)
+        });
+
+        self.check_suspicious()?;
+        Ok((buf, bounds))
+    }
+
+    /// Decodes the image into a packed mask with one bit per pixel, which is set if the pixel's alpha
+    /// is at least `threshold`. This is a compact format for collision masks and stencil buffers.
+    /// The given header must be the one returned alongside this decoder by `decode_header`, and no
+    /// pixels may have been decoded yet; panics otherwise.
+    ///
+    /// Each byte holds 8 horizontally adjacent pixels, with the leftmost pixel in the most
+    /// significant bit. Each row starts on a new byte, so a row takes `ceil(width / 8)` bytes and any
+    /// unused bits at the end of a row are zero.
+    #[cfg(feature = "alloc")]
+    pub fn decode_alpha_bitmask(
+        mut self,
+        header: &Header,
+        threshold: u8,
+    ) -> Result<Vec<u8>, DecodeAllError<S::IoError>> {
+        self.assert_matches_header(header);
+
+        let (width, height) = header.dimensions();
+
+        let width = usize::try_from(width).map_err(|_| DecodeAllError::TooLarge)?;
+        let height = usize::try_from(height).map_err(|_| DecodeAllError::TooLarge)?;
+
+        let row_bytes = (width / 8) + usize::from(width % 8 != 0);
+        let num_bytes = row_bytes.checked_mul(height)
+            .ok_or(DecodeAllError::TooLarge)?;
+
+        let mut mask = try_alloc_vec(num_bytes)?;
+        mask.resize(num_bytes, 0u8);
+
+        if width == 0 {
+            return Ok(mask);
+        }
+
+        let mut row = try_alloc_vec(width)?;
+        row.resize(width, false);
+
+        for mask_row in mask.chunks_exact_mut(row_bytes) {
+            self.decode_into_pixel_buf(&mut row, |pixel| pixel.a >= threshold)?;
+
+            for (byte, bits) in mask_row.iter_mut().zip(row.chunks(8)) {
+                *byte = bits
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (i, &bit)| byte | (u8::from(bit) << (7 - i)));
+            }
+        }
+
+        Ok(mask)
+    }
+
+    /// Decodes the remaining pixels and counts how many of them are each distinct color, without
+    /// storing the pixels themselves. Every pixel of a run is counted at once. Returns `Ok(None)` as
+    /// soon as more than `max_colors` distinct colors have been seen, which bounds the memory used
+    /// for images with many colors, such as photographs.
+    #[cfg(feature = "std")]
+    pub fn decode_palette(
+        mut self,
+        max_colors: usize,
+    ) -> Result<Option<HashMap<Pixel, u64>>, DecodeAllError<S::IoError>> {
+        let expected = self.remaining;
+        let mut palette = HashMap::new();
+
+        while self.remaining > 0 {
+            let pixel = self.decode_next()
+                .map_err(|err| self.truncated_error(err.into(), expected))?;
+
+            // A run may not go past the end of the image, but its excess pixels are never decoded
+            // so they are not counted either
+            let count = (1 + self.run as u64).min(self.remaining);
+            self.run = 0;
+            self.remaining -= count;
+
+            *palette.entry(pixel).or_insert(0) += count;
+
+            if palette.len() > max_colors {
+                return Ok(None);
+            }
+        }
+
+        self.check_suspicious()?;
+        Ok(Some(palette))
+    }
+
+    /// Decodes the image and returns the number of bytes of chunk data used to encode each row, which
+    /// is useful for finding which parts of an image compress poorly. A QOI_OP_RUN chunk counts
+    /// towards the row it starts in, even if the run continues onto later rows. The header and end
+    /// marker are not included in any row's count. The given header must be the one returned
+    /// alongside this decoder by `decode_header`, and no pixels may have been decoded yet; panics
+    /// otherwise.
+    #[cfg(feature = "alloc")]
+    pub fn decode_row_byte_counts(
+        mut self,
+        header: &Header,
+    ) -> Result<Vec<u64>, DecodeAllError<S::IoError>> {
+        self.assert_matches_header(header);
+
+        let (width, height) = header.dimensions();
+
+        let mut counts = try_alloc_vec(height.try_into().map_err(|_| DecodeAllError::TooLarge)?)?;
+
+        for _ in 0..height {
+            let mut count = 0;
+
+            for _ in 0..width {
+                if self.run > 0 {
+                    self.run -= 1;
+                    continue;
+                }
+
+                let b0 = self.decode_next_chunk()?;
+                count += chunk_len(b0) as u64;
+            }
+
+            counts.push(count);
+        }
+
+        self.remaining = 0;
+
+        Ok(counts)
+    }
+
+    /// Decodes pixels until the end marker or the end of the stream is reached, ignoring the number of
+    /// pixels the header says the image contains. This is useful for recovering files whose header is
+    /// corrupt or whose pixel data is truncated. The returned `bool` is true if decoding stopped
+    /// because the end marker was found, and false if it stopped because the stream ended. The
+    /// returned `u64` is the number of bytes read from the stream up to the end of the last complete
+    /// chunk, not counting the end marker or any chunk which the stream ended partway through. It is
+    /// `None` if the stream does not count the bytes it consumes.
+    ///
+    /// The end marker is detected as seven `0x00` chunks followed by a `0x01` chunk. Since these are
+    /// also valid QOI_OP_INDEX chunks, an unusual encoder could in principle produce the same sequence
+    /// in the middle of an image, in which case decoding will stop early. Canonical encoders never do
+    /// so, because they would encode the repeated pixels as a run instead.
+    ///
+    /// The output vec grows as pixels are decoded rather than being sized from the header, so there is
+    /// no upper bound on how much memory this may use other than the length of the stream.
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::type_complexity)]
+    pub fn decode_pixels_vec_lenient(
+        mut self,
+    ) -> Result<(Vec<Pixel>, bool, Option<u64>), DecodeAllError<S::IoError>> {
+        let mut buf = Vec::new();
+        let mut zero_chunks = 0usize;
+        let mut chunks_end = self.stream.bytes_consumed();
+
+        loop {
+            if self.run > 0 {
+                self.run -= 1;
+                buf.push(self.previous);
+                continue;
+            }
+
+            self.begin_chunk();
+
+            let b0 = match self.read_tag() {
+                Ok(b0) => b0,
+                Err(StreamError::UnexpectedEof) => return Ok((buf, false, chunks_end)),
+                Err(err) => return Err(err.into()),
+            };
+
+            if b0 == Header::END_MARKER[7] && zero_chunks >= 7 {
+                // The last seven "pixels" were actually the start of the end marker, each of which
+                // was a single byte
+                buf.truncate(buf.len() - 7);
+                return Ok((buf, true, chunks_end.map(|end| end - 7)));
+            }
+
+            if b0 == 0x00 {
+                zero_chunks += 1;
+            } else {
+                zero_chunks = 0;
+            }
+
+            match self.decode_chunk(b0) {
+                Ok(()) => (),
+                Err(StreamError::UnexpectedEof) => return Ok((buf, false, chunks_end)),
+                Err(err) => return Err(err.into()),
+            }
+
+            self.end_chunk();
+
+            chunks_end = self.stream.bytes_consumed();
+            buf.push(self.previous);
         }
     }
 
-    /// Decodes pixels into the given buffer until the buffer becomes full or the end of the image is
-    /// reached, whichever comes first. The returned `usize` is the number of pixels written to the
-    /// buffer. The returned `bool` is true if all of the pixels have been decoded after this operation,
-    /// and false if there are still more pixels that need to be decoded after this operation.
-    pub fn decode_pixels_into(
+    /// Decodes all of the remaining pixels without storing them, followed by the end marker and any
+    /// trailing bytes, and adds any problems found to `issues`. `offset` is the position in the file
+    /// of the first chunk, which is used to report the positions of problems.
+    #[cfg(feature = "alloc")]
+    fn validate_chunks(
         &mut self,
-        buf: &mut [Pixel],
-    ) -> Result<(usize, bool), StreamError<S::IoError>> {
-        self.decode_into_pixel_buf(buf, convert::identity)
-    }
+        mut offset: u64,
+        check_canonical: bool,
+        issues: &mut Vec<ValidationIssue>,
+    ) -> Result<(), S::IoError> {
+        let num_pixels = self.remaining;
+        let mut pixel = 0u64;
 
-    /// Allocates a new vec large enough for all of the remaining pixels, decodes all of the remaining
-    /// pixels into the vec, and returns the buffer. Returns a `DecodeAllError::TooLarge` if allocating
-    /// a vec large enough is not possible.
-    pub fn decode_pixels_vec(mut self) -> Result<Vec<Pixel>, DecodeAllError<S::IoError>> {
-        let num_pixels = self.remaining.try_into().map_err(|_| DecodeAllError::TooLarge)?;
+        // Whether the last chunk was a QOI_OP_RUN shorter than the maximum run length
+        let mut after_short_run = false;
 
-        let mut buf = Vec::new();
-        buf.try_reserve_exact(num_pixels)
-            .map_err(|_| DecodeAllError::TooLarge)?;
+        // The offset and pixel number of the first non-canonical chunk, and the number of them
+        let mut first_non_canonical = None;
+        let mut num_non_canonical = 0u64;
 
-        let ptr = buf.as_mut_ptr();
-        let dst = unsafe { slice::from_raw_parts_mut(ptr, num_pixels) };
+        while pixel < num_pixels {
+            let previous = self.previous;
+            let index = if check_canonical { Some(self.index.clone()) } else { None };
 
-        let (n, exhausted) = self.decode_into_pixel_buf(dst, convert::identity)?;
+            self.begin_chunk();
 
-        unsafe {
-            buf.set_len(n);
-        }
+            let decoded = match until_eof(self.read_tag())? {
+                Some(b0) => until_eof(self.decode_chunk(b0))?.map(|()| b0),
+                None => None,
+            };
 
-        debug_assert!(exhausted);
+            let b0 = match decoded {
+                Some(b0) => b0,
+                None => {
+                    issues.push(ValidationIssue::TruncatedPixels {
+                        offset,
+                        expected: num_pixels,
+                        found: pixel,
+                    });
+                    self.remaining = 0;
+                    return Ok(());
+                }
+            };
 
-        Ok(buf)
-    }
+            self.end_chunk();
 
-    pub fn decode_bytes_into<F, const N: usize>(
-        &mut self,
-        buf: &mut [u8],
-        transform: F,
-    ) -> Result<(usize, bool), StreamError<S::IoError>>
-    where
-        F: Fn(Pixel) -> [u8; N],
-    {
-        assert!(N != 0);
+            if let Some(index) = index {
+                if !is_canonical(b0, previous, self.previous, &index, after_short_run) {
+                    first_non_canonical.get_or_insert((offset, pixel));
+                    num_non_canonical += 1;
+                }
+            }
 
-        let num_pixels = buf.len() / N;
+            let op = ChunkOp::from_tag(b0);
+            after_short_run = op == ChunkOp::Run && self.run < MAX_RUN - 1;
 
-        let ptr = buf.as_mut_ptr() as *mut [u8; N];
-        let dst = unsafe { slice::from_raw_parts_mut(ptr, num_pixels) };
+            // Take every pixel of a run at once
+            pixel += 1 + self.run as u64;
+            self.run = 0;
 
-        self.decode_into_pixel_buf(dst, transform)
-            .map(|(n, exhausted)| (n * N, exhausted))
-    }
+            if pixel > num_pixels {
+                issues.push(ValidationIssue::RunPastEnd {
+                    offset,
+                    excess: pixel - num_pixels,
+                });
+            }
 
-    pub fn decode_bytes_vec<F, const N: usize>(
-        mut self,
-        transform: F,
-    ) -> Result<Vec<u8>, DecodeAllError<S::IoError>>
-    where
-        F: Fn(Pixel) -> [u8; N],
-    {
-        assert!(N != 0);
+            offset += chunk_len(b0) as u64;
+        }
 
-        let num_pixels = usize::try_from(self.remaining)
-            .map_err(|_| DecodeAllError::TooLarge)?;
+        self.remaining = 0;
 
-        let num_bytes = num_pixels.checked_mul(N)
-            .ok_or(DecodeAllError::TooLarge)?;
+        if let Some((offset, pixel)) = first_non_canonical {
+            issues.push(ValidationIssue::NonCanonical {
+                offset,
+                pixel,
+                count: num_non_canonical,
+            });
+        }
 
-        let mut buf = Vec::new();
-        buf.try_reserve_exact(num_bytes)
-            .map_err(|_| DecodeAllError::TooLarge)?;
+        let mut end_marker = Vec::with_capacity(Header::END_MARKER.len());
+        while end_marker.len() < Header::END_MARKER.len() {
+            match until_eof(self.stream.read_one())? {
+                Some(byte) => end_marker.push(byte),
+                None => break,
+            }
+        }
+
+        let end_marker_len = end_marker.len();
 
-        let ptr = buf.as_mut_ptr() as *mut [u8; N];
-        let dst = unsafe { slice::from_raw_parts_mut(ptr, num_pixels) };
+        if end_marker != Header::END_MARKER {
+            issues.push(ValidationIssue::BadEndMarker {
+                offset,
+                found: end_marker,
+            });
+        }
 
-        let (n, exhausted) = self.decode_into_pixel_buf(dst, transform)?;
+        offset += end_marker_len as u64;
 
-        unsafe {
-            buf.set_len(n * N);
+        let mut num_trailing = 0u64;
+        if end_marker_len == Header::END_MARKER.len() {
+            while until_eof(self.stream.read_one())?.is_some() {
+                num_trailing += 1;
+            }
         }
 
-        debug_assert!(exhausted);
+        if num_trailing > 0 {
+            issues.push(ValidationIssue::TrailingBytes {
+                offset,
+                len: num_trailing,
+            });
+        }
 
-        Ok(buf)
+        Ok(())
     }
 
     /// Returns the number of pixels remaining to be decoded. This is an upper bound on how many more
@@ -189,110 +1676,640 @@ where
         self.remaining
     }
 
+    /// Panics if the given header does not describe exactly the number of pixels remaining, which
+    /// means either that it is not the header this decoder was created from or that some pixels have
+    /// already been decoded.
+    #[cfg(feature = "alloc")]
+    fn assert_matches_header(&self, header: &Header) {
+        assert_eq!(
+            header.width() as u64 * header.height() as u64,
+            self.remaining,
+            "header dimensions do not match the number of pixels remaining"
+        );
+    }
+
+    /// Converts `DecodeAllError::UnexpectedEof` into `DecodeAllError::Truncated`, given the number
+    /// of pixels which were remaining when decoding started.
+    #[cfg(feature = "alloc")]
+    fn truncated_error(
+        &self,
+        err: DecodeAllError<S::IoError>,
+        expected: u64,
+    ) -> DecodeAllError<S::IoError> {
+        match err {
+            DecodeAllError::UnexpectedEof => DecodeAllError::Truncated {
+                decoded: expected - self.remaining,
+                expected,
+            },
+            err => err,
+        }
+    }
+
+    /// Allocates a new vec with space for `num_pixels` pixels, and decodes pixels into it until it is
+    /// full or the end of the image is reached, applying `transform` to each pixel.
+    #[cfg(feature = "alloc")]
+    fn decode_pixels_vec_n<F>(
+        &mut self,
+        num_pixels: usize,
+        transform: F,
+    ) -> Result<Vec<Pixel>, DecodeAllError<S::IoError>>
+    where
+        F: Fn(Pixel) -> Pixel,
+    {
+        let buf = decode_into_new_vec(num_pixels, Pixel::ZERO, |dst| {
+            let (n, _) = self.decode_into_pixel_buf(dst, transform)?;
+            Ok(n)
+        })?;
+
+        self.check_suspicious()?;
+        Ok(buf)
+    }
+
     fn decode_into_pixel_buf<T, F>(
         &mut self,
         buf: &mut [T],
         transform: F,
     ) -> Result<(usize, bool), StreamError<S::IoError>>
     where
+        T: Copy,
         F: Fn(Pixel) -> T,
     {
-        let (num_pixels, exhausted) = if !usize::try_from(self.remaining).is_ok() || buf.len() < self.remaining as usize {
+        let (num_pixels, exhausted) = if usize::try_from(self.remaining).is_err() || buf.len() < self.remaining as usize {
             (buf.len(), false)
         } else {
             (self.remaining as usize, true)
         };
 
-        for i in 0..num_pixels {
+        let buf = &mut buf[..num_pixels];
+        let mut pos = 0;
+
+        while pos < num_pixels {
             if self.run > 0 {
-                self.run -= 1;
-                buf[i] = transform(self.previous);
+                // Write the rest of the run with a single fill rather than one pixel at a time, which
+                // the compiler can turn into a vectorised loop
+                let len = usize::from(self.run).min(num_pixels - pos);
+                buf[pos..(pos + len)].fill(transform(self.previous));
+                self.run -= len as u8;
+                pos += len;
                 continue;
             }
 
-            let b0 = self.stream.read_one()?;
+            match self.decode_next() {
+                Ok(pixel) => buf[pos] = transform(pixel),
+                Err(err) => {
+                    // Keep `remaining` accurate so that callers can tell how far decoding got
+                    self.remaining -= pos as u64;
+                    return Err(err);
+                }
+            }
+
+            pos += 1;
+        }
+
+        self.remaining -= num_pixels as u64;
+        Ok((num_pixels, exhausted))
+    }
+
+    /// Decodes the next pixel, either by continuing the current run or by decoding the next chunk.
+    /// Does not check or update `remaining`.
+    #[inline(always)]
+    fn decode_next(&mut self) -> Result<Pixel, StreamError<S::IoError>> {
+        if self.run > 0 {
+            self.run -= 1;
+            return Ok(self.previous);
+        }
+
+        self.decode_next_chunk()?;
+        Ok(self.previous)
+    }
+
+    /// Reads and decodes the next chunk, returning its tag byte.
+    ///
+    /// `decode_chunk` reads all of a chunk's bytes before it changes any state, so if the stream
+    /// ends partway through the chunk, the only thing lost is the tag byte. The tag is kept in
+    /// `pending_tag` in that case, so that decoding the chunk can be retried once more data is
+    /// available. This only leaves the decoder exactly as it was if the stream consumes nothing
+    /// when `read_n` fails with `UnexpectedEof`.
+    #[inline(always)]
+    fn decode_next_chunk(&mut self) -> Result<u8, StreamError<S::IoError>> {
+        self.begin_chunk();
+        let b0 = self.read_tag()?;
+
+        if let Err(err) = self.decode_chunk(b0) {
+            self.pending_tag = Some(b0);
+            return Err(err);
+        }
+
+        self.end_chunk();
+        Ok(b0)
+    }
+
+    /// Handles a QOI_OP_INDEX chunk which referred to an index slot that has never been written, by
+    /// recording it in strict mode and substituting the fallback pixel if there is one.
+    #[cold]
+    fn unwritten_index_slot(&mut self) {
+        if self.strict && !self.suspicious {
+            self.suspicious = true;
+            // The chunk is a single byte, which was the last one read
+            self.suspicious_offset = self.stream.bytes_consumed().map(|consumed| consumed - 1);
+        }
+
+        if let Some(fallback) = self.index_fallback {
+            self.previous = fallback;
+        }
+    }
+
+    /// Returns `DecodeAllError::SuspiciousStream` if strict mode found a suspicious chunk.
+    #[cfg(feature = "alloc")]
+    fn check_suspicious(&self) -> Result<(), DecodeAllError<S::IoError>> {
+        if self.suspicious {
+            Err(DecodeAllError::SuspiciousStream {
+                offset: self.suspicious_offset,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads the tag byte of the next chunk, or takes the tag of a chunk which could not be decoded
+    /// earlier because the stream ended partway through it.
+    #[inline(always)]
+    fn read_tag(&mut self) -> Result<u8, StreamError<S::IoError>> {
+        match self.pending_tag.take() {
+            Some(b0) => Ok(b0),
+            None => self.stream.read_one(),
+        }
+    }
+
+    /// Decodes the chunk starting with the tag byte `b0`, reading any further bytes the chunk needs
+    /// from the stream and updating `previous`, `index` and `run` accordingly.
+    #[inline(always)]
+    fn decode_chunk(&mut self, b0: u8) -> Result<(), StreamError<S::IoError>> {
+        // QOI_OP_RUN is tested for before anything else, since it is the most common chunk in many
+        // images. In `bench_op_breakdown` this made QOI_OP_INDEX and QOI_OP_RGBA chunks cheaper
+        // without making any other kind of chunk slower, and it also sped up the mixed image in
+        // `bench_pixels_slice`.
+        //
+        // Runs repeat the previous pixel without inserting it, which matches the `qoi` crate. The
+        // previous pixel was already inserted by the chunk which produced it, unless it is the
+        // initial `Pixel::BLACK` or came from an unwritten index slot. For the initial pixel, the
+        // reference C decoder inserts it into slot 53 after a leading run and this decoder does
+        // not, so the two disagree about that slot until it is next written. Canonical encoders
+        // never refer to it in the meantime.
+        if (0xC0..0xFE).contains(&b0) {
+            self.run = b0 & 0x3F;
+            return Ok(());
+        }
+
+        match b0 {
+            // QOI_OP_RGB
+            0xFE => {
+                let [r, g, b] = self.stream.read_n()?;
+                self.previous.r = r;
+                self.previous.g = g;
+                self.previous.b = b;
+                self.index.insert(self.previous);
+            }
+
+            // QOI_OP_RGBA
+            0xFF => {
+                let [r, g, b, a] = self.stream.read_n()?;
+                self.previous = Pixel::new(r, g, b, a);
+                self.index.insert(self.previous);
+            }
+
+            _ => match b0 >> 6 {
+                // QOI_OP_INDEX
+                // The pixel is not inserted again, which matches the `qoi` crate. If the slot has
+                // been written, the pixel is already stored at the slot its hash refers to, so this
+                // makes no difference. An unwritten slot other than 0 gives `Pixel::ZERO`, whose
+                // hash refers to slot 0, so the reference C decoder, which inserts after every
+                // chunk, would overwrite slot 0 here and this decoder does not. Canonical encoders
+                // never refer to those slots
+                0x0 => {
+                    self.previous = self.index.masked_get(b0 as usize);
+
+                    // See `DecoderOptions::index_fallback` for why this means the slot is unwritten
+                    if self.previous == Pixel::ZERO && b0 != 0 {
+                        self.unwritten_index_slot();
+                    }
+                }
 
-            match b0 {
-                // QOI_OP_RGB
-                0xFE => {
-                    let [r, g, b] = self.stream.read_n()?;
-                    self.previous.r = r;
-                    self.previous.g = g;
-                    self.previous.b = b;
+                // QOI_OP_DIFF
+                0x1 => {
+                    self.previous.r = self
+                        .previous
+                        .r
+                        .wrapping_sub(2)
+                        .wrapping_add((b0 >> 4) & 0x3);
+                    self.previous.g = self
+                        .previous
+                        .g
+                        .wrapping_sub(2)
+                        .wrapping_add((b0 >> 2) & 0x3);
+                    self.previous.b = self.previous.b.wrapping_sub(2).wrapping_add(b0 & 0x3);
                     self.index.insert(self.previous);
                 }
 
-                // QOI_OP_RGBA
-                0xFF => {
-                    let [r, g, b, a] = self.stream.read_n()?;
-                    self.previous = Pixel::new(r, g, b, a);
+                // QOI_OP_LUMA, the only chunk left once runs have been ruled out
+                _ => {
+                    let b1 = self.stream.read_one()?;
+                    let dg = (b0 & 0x3F).wrapping_sub(32);
+                    self.previous.r = self
+                        .previous
+                        .r
+                        .wrapping_add(dg)
+                        .wrapping_sub(8)
+                        .wrapping_add((b1 >> 4) & 0x0F);
+                    self.previous.g = self.previous.g.wrapping_add(dg);
+                    self.previous.b = self
+                        .previous
+                        .b
+                        .wrapping_add(dg)
+                        .wrapping_sub(8)
+                        .wrapping_add(b1 & 0x0F);
                     self.index.insert(self.previous);
                 }
+            },
+        }
 
-                _ => match b0 >> 6 {
-                    // QOI_OP_INDEX
-                    0x0 => {
-                        self.previous = self.index.masked_get(b0);
-                    }
+        Ok(())
+    }
 
-                    // QOI_OP_DIFF
-                    0x1 => {
-                        self.previous.r = self
-                            .previous
-                            .r
-                            .wrapping_sub(2)
-                            .wrapping_add((b0 >> 4) & 0x3);
-                        self.previous.g = self
-                            .previous
-                            .g
-                            .wrapping_sub(2)
-                            .wrapping_add((b0 >> 2) & 0x3);
-                        self.previous.b = self.previous.b.wrapping_sub(2).wrapping_add(b0 & 0x3);
-                        self.index.insert(self.previous);
-                    }
+    #[inline(always)]
+    fn begin_chunk(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            self.chunk_start = self.stream.bytes_consumed();
+        }
+    }
 
-                    // QOI_OP_LUMA
-                    0x2 => {
-                        let b1 = self.stream.read_one()?;
-                        let dg = (b0 & 0x3F).wrapping_sub(32);
-                        self.previous.r = self
-                            .previous
-                            .r
-                            .wrapping_add(dg)
-                            .wrapping_sub(8)
-                            .wrapping_add((b1 >> 4) & 0x0F);
-                        self.previous.g = self.previous.g.wrapping_add(dg);
-                        self.previous.b = self
-                            .previous
-                            .b
-                            .wrapping_add(dg)
-                            .wrapping_sub(8)
-                            .wrapping_add(b1 & 0x0F);
-                        self.index.insert(self.previous);
-                    }
+    /// In debug builds, checks that the stream did not go backwards while the chunk that was just
+    /// decoded was being read, and that the chunk did not consume more bytes than the largest chunk
+    /// the spec allows. Either indicates a bug in the decoder or the stream rather than bad input, so
+    /// it panics rather than returning an error. Nothing is checked if the stream does not count the
+    /// bytes it consumes.
+    #[inline(always)]
+    fn end_chunk(&self) {
+        #[cfg(debug_assertions)]
+        {
+            if let (Some(start), Some(end)) = (self.chunk_start, self.stream.bytes_consumed()) {
+                assert!(
+                    end >= start,
+                    "stream went backwards from byte {} to byte {} while decoding a chunk",
+                    start,
+                    end
+                );
+                assert!(
+                    end - start <= MAX_CHUNK_LEN as u64,
+                    "decoder consumed {} bytes for a single chunk, but the maximum is {}",
+                    end - start,
+                    MAX_CHUNK_LEN
+                );
+            }
+        }
+    }
+}
 
-                    // QOI_OP_RUN
-                    _ => {
-                        self.run = b0 & 0x3F;
-                    }
-                },
+/// Returns the total length in bytes of a chunk, including the tag byte `b0`.
+fn chunk_len(b0: u8) -> usize {
+    match b0 {
+        0xFE => 4,
+        0xFF => 5,
+        _ if b0 >> 6 == 0x2 => 2,
+        _ => 1,
+    }
+}
+
+/// The kinds of chunk, as determined by their tag byte.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg(feature = "alloc")]
+enum ChunkOp {
+    Rgb,
+    Rgba,
+    Index,
+    Diff,
+    Luma,
+    Run,
+}
+
+#[cfg(feature = "alloc")]
+impl ChunkOp {
+    fn from_tag(b0: u8) -> Self {
+        match b0 {
+            0xFE => Self::Rgb,
+            0xFF => Self::Rgba,
+            _ => match b0 >> 6 {
+                0x0 => Self::Index,
+                0x1 => Self::Diff,
+                0x2 => Self::Luma,
+                _ => Self::Run,
+            },
+        }
+    }
+}
+
+/// Returns true if the chunk starting with the tag byte `b0`, which changed the current pixel from
+/// `previous` to `pixel`, is the chunk the reference encoder would have used. `index` is the pixel
+/// index before the chunk was decoded, and `after_short_run` is true if the chunk before this one
+/// was a QOI_OP_RUN shorter than the maximum run length.
+#[cfg(feature = "alloc")]
+fn is_canonical(
+    b0: u8,
+    previous: Pixel,
+    pixel: Pixel,
+    index: &QoiIndex,
+    after_short_run: bool,
+) -> bool {
+    let op = ChunkOp::from_tag(b0);
+
+    // The reference encoder always encodes repeats of the previous pixel as a run, and only ends a
+    // run early when the next pixel is different or the image ends
+    if pixel == previous {
+        return op == ChunkOp::Run && !after_short_run;
+    }
+
+    let expected = if index.contains(pixel) {
+        ChunkOp::Index
+    } else if pixel.diff_chunk(previous).is_some() {
+        ChunkOp::Diff
+    } else if pixel.luma_chunk(previous).is_some() {
+        ChunkOp::Luma
+    } else if pixel.a == previous.a {
+        ChunkOp::Rgb
+    } else {
+        ChunkOp::Rgba
+    };
+
+    op == expected
+}
+
+/// Converts an unexpected end of the stream into `Ok(None)`, so that it can be handled separately
+/// from I/O errors.
+#[cfg(feature = "alloc")]
+fn until_eof<T, E>(res: Result<T, StreamError<E>>) -> Result<Option<T>, E> {
+    match res {
+        Ok(x) => Ok(Some(x)),
+        Err(StreamError::UnexpectedEof) => Ok(None),
+        Err(StreamError::Io(err)) => Err(err),
+    }
+}
+
+/// Returns a `DecodeAllError::TooLarge` if `len` elements of `T` would take up more bytes than an
+/// allocation is allowed to have.
+#[cfg(feature = "alloc")]
+fn check_alloc_len<T, E>(len: usize) -> Result<(), DecodeAllError<E>> {
+    len.checked_mul(mem::size_of::<T>())
+        .filter(|&num_bytes| num_bytes <= isize::MAX as usize)
+        .map(|_| ())
+        .ok_or(DecodeAllError::TooLarge)
+}
+
+/// Creates an empty vec with capacity for exactly `len` elements. Returns a
+/// `DecodeAllError::TooLarge` if the capacity would overflow, or a `DecodeAllError::Alloc` if the
+/// allocator fails.
+#[cfg(feature = "alloc")]
+fn try_alloc_vec<T, E>(len: usize) -> Result<Vec<T>, DecodeAllError<E>> {
+    check_alloc_len::<T, E>(len)?;
+    let mut buf = Vec::new();
+    buf.try_reserve_exact(len).map_err(DecodeAllError::Alloc)?;
+    Ok(buf)
+}
+
+/// Allocates a new vec with space for `len` elements and calls `decode` with a slice of length `len`
+/// over the vec's memory. `decode` returns how many elements it wrote to the start of the slice,
+/// which becomes the length of the returned vec.
+///
+/// By default, the slice is over the vec's uninitialised spare capacity, so `decode` must not read
+/// from it. With the `safe_only` feature, the vec is first filled with `fill` so that no unsafe code
+/// is needed, at the cost of writing the whole buffer an extra time.
+#[cfg(feature = "alloc")]
+pub(crate) fn decode_into_new_vec<T, E, F>(len: usize, fill: T, decode: F) -> Result<Vec<T>, DecodeAllError<E>>
+where
+    T: Copy,
+    F: FnOnce(&mut [T]) -> Result<usize, DecodeAllError<E>>,
+{
+    let mut buf = try_alloc_vec(len)?;
+
+    #[cfg(not(feature = "safe_only"))]
+    {
+        let _ = fill;
+
+        let ptr = buf.as_mut_ptr();
+        let dst = unsafe { slice::from_raw_parts_mut(ptr, len) };
+
+        let n = decode(dst)?;
+        debug_assert!(n <= len);
+
+        unsafe {
+            buf.set_len(n);
+        }
+    }
+
+    #[cfg(feature = "safe_only")]
+    {
+        buf.resize(len, fill);
+        let n = decode(&mut buf)?;
+        buf.truncate(n);
+    }
+
+    Ok(buf)
+}
+
+#[cfg(feature = "crc")]
+impl<S> PixelDecoder<CrcByteStream<S>>
+where
+    S: ByteStream,
+{
+    /// Skips any pixels which have not been decoded yet, then reads the end marker and the CRC-32
+    /// following it, as described in the `crc` module. Returns `DecodeAllError::CrcMismatch` if the
+    /// CRC does not match the bytes read, or `DecodeAllError::BadEndMarker` if there is no end
+    /// marker. A file without a CRC fails with `DecodeAllError::UnexpectedEof`.
+    pub fn verify_crc(mut self) -> Result<(), DecodeAllError<S::IoError>> {
+        self.skip_pixels(u64::MAX)?;
+
+        let end_marker = self.stream.read_n::<8>()?;
+        if end_marker != Header::END_MARKER {
+            return Err(DecodeAllError::BadEndMarker { found: end_marker });
+        }
+
+        let expected = self.stream.crc();
+        let found = u32::from_be_bytes(self.stream.read_n()?);
+        if found != expected {
+            return Err(DecodeAllError::CrcMismatch { expected, found });
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes one pixel at a time. After an error, the iterator returns `None`, since the position in
+/// the stream is no longer known. The bulk decoding methods are considerably faster than iterating,
+/// so prefer them where the pixels are going to end up in a buffer anyway.
+impl<S> Iterator for PixelDecoder<S>
+where
+    S: ByteStream,
+{
+    type Item = Result<Pixel, StreamError<S::IoError>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.decode_pixel() {
+            Ok(pixel) => pixel.map(Ok),
+            Err(err) => {
+                self.remaining = 0;
+                Some(Err(err))
             }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, usize::try_from(self.remaining).ok())
+    }
+}
+
+/// An iterator which decodes one pixel at a time from a `PixelDecoder`. Once an error is returned,
+/// the iterator will return `None` for every subsequent call to `next`.
+pub struct Pixels<S> {
+    decoder: PixelDecoder<S>,
+}
+
+impl<S> Pixels<S>
+where
+    S: ByteStream,
+{
+    pub fn new(decoder: PixelDecoder<S>) -> Self {
+        Self { decoder }
+    }
+
+    pub fn into_inner(self) -> PixelDecoder<S> {
+        self.decoder
+    }
+}
+
+impl<S> Iterator for Pixels<S>
+where
+    S: ByteStream,
+{
+    type Item = Result<Pixel, StreamError<S::IoError>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.decoder.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.decoder.size_hint()
+    }
+}
+
+/// An `io::Read` over the bytes of decoded pixels, returned by `PixelDecoder::into_byte_reader`.
+#[cfg(feature = "std")]
+pub struct ByteReader<S, F, const N: usize> {
+    decoder: PixelDecoder<S>,
+    transform: F,
+    /// The bytes of a pixel which did not fit in the buffer of an earlier read.
+    carry: [u8; N],
+    /// The position in `carry` of the first byte which has not been read yet. `N` if there are no
+    /// bytes left in `carry`.
+    carry_pos: usize,
+}
+
+#[cfg(feature = "std")]
+impl<S, F, const N: usize> ByteReader<S, F, N>
+where
+    S: ByteStream,
+    F: Fn(Pixel) -> [u8; N],
+{
+    fn new(decoder: PixelDecoder<S>, transform: F) -> Self {
+        assert!(N != 0);
 
-            buf[i] = transform(self.previous);
+        Self {
+            decoder,
+            transform,
+            carry: [0; N],
+            carry_pos: N,
         }
+    }
 
-        self.remaining -= num_pixels as u64;
-        Ok((num_pixels, exhausted))
+    pub fn into_inner(self) -> PixelDecoder<S> {
+        self.decoder
+    }
+
+    /// Copies as much of the carried-over pixel as will fit into `buf`.
+    fn read_carry(&mut self, buf: &mut [u8]) -> usize {
+        let carried = &self.carry[self.carry_pos..];
+        let len = carried.len().min(buf.len());
+        buf[..len].copy_from_slice(&carried[..len]);
+        self.carry_pos += len;
+        len
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S, F, const N: usize> io::Read for ByteReader<S, F, N>
+where
+    S: ByteStream,
+    S::IoError: error::Error + Send + Sync + 'static,
+    F: Fn(Pixel) -> [u8; N],
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.carry_pos < N {
+            return Ok(self.read_carry(buf));
+        }
+
+        let whole_pixels_len = buf.len() - buf.len() % N;
+
+        if whole_pixels_len > 0 {
+            let (len, _) = self.decoder
+                .decode_bytes_into(&mut buf[..whole_pixels_len], &self.transform)
+                .map_err(stream_io_error)?;
+            return Ok(len);
+        }
+
+        // The buffer is smaller than a pixel, so decode the next pixel into the carry buffer and
+        // return as much of it as fits
+        match self.decoder.decode_pixel().map_err(stream_io_error)? {
+            Some(pixel) => {
+                self.carry = (self.transform)(pixel);
+                self.carry_pos = 0;
+                Ok(self.read_carry(buf))
+            }
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn stream_io_error<E>(err: StreamError<E>) -> io::Error
+where
+    E: error::Error + Send + Sync + 'static,
+{
+    match err {
+        StreamError::UnexpectedEof => io::ErrorKind::UnexpectedEof.into(),
+        StreamError::Io(err) => io::Error::new(io::ErrorKind::Other, err),
     }
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum HeaderDecodeError<E> {
     UnexpectedEof,
     Io(E),
     Magic(header::MagicError),
     Channels(header::ChannelsError),
     ColSpace(header::ColSpaceError),
+    TooLarge { megapixels: f64 },
+    /// The image has a width or height of zero, and the decoder's options do not allow this.
+    EmptyImage,
+    /// The magic bytes are not those of the variant described in the `wide_index` module.
+    #[cfg(feature = "wide_index")]
+    NotWide { magic: [u8; 4] },
+    /// The file was written with an index of `2^found` slots, but is being decoded with `2^expected`.
+    #[cfg(feature = "wide_index")]
+    IndexSize { expected: u8, found: u8 },
 }
 
 impl<E> fmt::Display for HeaderDecodeError<E>
@@ -306,10 +2323,28 @@ where
             Self::Magic(err) => err.fmt(f),
             Self::Channels(err) => err.fmt(f),
             Self::ColSpace(err) => err.fmt(f),
+            Self::TooLarge { megapixels } => {
+                write!(f, "image too large ({:.2} megapixels)", megapixels)
+            }
+            Self::EmptyImage => f.write_str("image has no pixels"),
+            #[cfg(feature = "wide_index")]
+            Self::NotWide { magic } => write!(
+                f,
+                "bad magic bytes {}, expected {}",
+                HexBytes::new(magic),
+                HexBytes::new(&wide_index::MAGIC)
+            ),
+            #[cfg(feature = "wide_index")]
+            Self::IndexSize { expected, found } => write!(
+                f,
+                "file uses an index of 2^{} slots, expected 2^{}",
+                found, expected
+            ),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl<E> error::Error for HeaderDecodeError<E> where E: error::Error {}
 
 impl<E> From<StreamError<E>> for HeaderDecodeError<E> {
@@ -340,9 +2375,22 @@ impl<E> From<header::ColSpaceError> for HeaderDecodeError<E> {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DecodeAllError<E> {
     UnexpectedEof,
     TooLarge,
+    #[cfg(feature = "alloc")]
+    Alloc(TryReserveError),
+    BadEndMarker { found: [u8; 8] },
+    /// The stream ended after `decoded` of the `expected` pixels had been decoded.
+    Truncated { decoded: u64, expected: u64 },
+    /// The CRC-32 following the end marker did not match the file. See the `crc` module.
+    #[cfg(feature = "crc")]
+    CrcMismatch { expected: u32, found: u32 },
+    /// In strict mode, a QOI_OP_INDEX chunk referred to an index slot which had never been written.
+    /// `offset` is the position of the chunk, if the stream counts the bytes it consumes. See
+    /// `DecoderOptions::strict`.
+    SuspiciousStream { offset: Option<u64> },
     Io(E),
 }
 
@@ -354,11 +2402,41 @@ where
         match self {
             Self::UnexpectedEof => f.write_str("unexpected eof"),
             Self::TooLarge => f.write_str("image too large"),
+            #[cfg(feature = "alloc")]
+            Self::Alloc(err) => err.fmt(f),
+            Self::BadEndMarker { found } => write!(
+                f,
+                "bad end marker {}, expected {}",
+                HexBytes::new(found),
+                HexBytes::new(&Header::END_MARKER)
+            ),
+            Self::Truncated { decoded, expected } => write!(
+                f,
+                "image truncated after {} of {} pixels",
+                decoded, expected
+            ),
+            #[cfg(feature = "crc")]
+            Self::CrcMismatch { expected, found } => write!(
+                f,
+                "crc mismatch, file has {:08X} but its contents have {:08X}",
+                found, expected
+            ),
+            Self::SuspiciousStream {
+                offset: Some(offset),
+            } => write!(
+                f,
+                "index chunk at offset {} refers to an unwritten index slot",
+                offset
+            ),
+            Self::SuspiciousStream { offset: None } => {
+                f.write_str("index chunk refers to an unwritten index slot")
+            }
             Self::Io(err) => err.fmt(f),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl<E> error::Error for DecodeAllError<E> where E: error::Error {}
 
 impl<E> From<StreamError<E>> for DecodeAllError<E> {
@@ -0,0 +1,181 @@
+use alloc::collections::TryReserveError;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead};
+
+use crate::byte_stream::SliceByteStream;
+use crate::header::Header;
+use crate::pixel::Pixel;
+
+use super::{Decoder, DecoderOptions, HeaderDecodeError, PixelDecoder, HEADER_LEN};
+
+/// A decoder for images which arrive in pieces, such as the byte ranges of an HTTP range request,
+/// which yields each row of the image as soon as enough bytes have arrived to decode it. Unlike
+/// the other decoders, running out of bytes is never an error; the decoder waits for more bytes to
+/// be pushed and resumes exactly where it stopped.
+///
+/// Only the bytes which have not yet been decoded are buffered, along with a single row of pixels.
+pub struct RangeDecoder {
+    options: DecoderOptions,
+    /// Bytes which have been pushed but not yet decoded start at `pos`.
+    buf: Vec<u8>,
+    pos: usize,
+    header: Option<Header>,
+    decoder: Option<PixelDecoder<()>>,
+    row: Vec<Pixel>,
+    /// The number of pixels of `row` decoded so far.
+    row_len: usize,
+    /// The index of the row currently being decoded.
+    y: u32,
+}
+
+impl RangeDecoder {
+    pub fn new() -> Self {
+        Self::with_options(DecoderOptions::default())
+    }
+
+    pub fn with_options(options: DecoderOptions) -> Self {
+        Self {
+            options,
+            buf: Vec::new(),
+            pos: 0,
+            header: None,
+            decoder: None,
+            row: Vec::new(),
+            row_len: 0,
+            y: 0,
+        }
+    }
+
+    /// Appends the next bytes of the file. Ranges must be pushed in order, with no gaps or overlaps
+    /// between them.
+    pub fn push(&mut self, bytes: &[u8]) {
+        // Discard the bytes which have already been decoded before growing the buffer
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Pushes the bytes currently in `reader`'s buffer, filling the buffer first if it is empty, and
+    /// returns the number of bytes pushed. Returns 0 if the reader has no more bytes.
+    #[cfg(feature = "std")]
+    pub fn push_from(&mut self, reader: &mut dyn BufRead) -> io::Result<usize> {
+        let bytes = reader.fill_buf()?;
+        let len = bytes.len();
+        self.push(bytes);
+        reader.consume(len);
+        Ok(len)
+    }
+
+    /// Returns the image's header, or `None` if not enough bytes have been pushed to decode it yet.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Returns true once every row of the image has been returned by `next_row`.
+    pub fn is_finished(&self) -> bool {
+        matches!(&self.header, Some(header) if self.y >= header.height())
+    }
+
+    /// Decodes as much of the image as the bytes pushed so far allow, and returns the index of the
+    /// next row and its pixels if that row is now complete. Returns `None` if more bytes are needed
+    /// to complete the next row, or if every row has already been returned.
+    ///
+    /// Bytes after the last pixel, such as the end marker, are ignored.
+    pub fn next_row(&mut self) -> Result<Option<(u32, &[Pixel])>, RangeDecodeError> {
+        if self.decoder.is_none() && !self.decode_header()? {
+            return Ok(None);
+        }
+
+        let height = match &self.header {
+            Some(header) => header.height(),
+            None => return Ok(None),
+        };
+
+        if self.y >= height {
+            return Ok(None);
+        }
+
+        let decoder = match self.decoder.take() {
+            Some(decoder) => decoder,
+            None => return Ok(None),
+        };
+
+        let bytes = &self.buf[self.pos..];
+        let mut decoder = decoder.with_stream(SliceByteStream::new(bytes));
+
+        self.row_len += decoder.decode_complete_chunks_into(&mut self.row[self.row_len..]);
+        self.pos += bytes.len() - decoder.stream.inner().len();
+        self.decoder = Some(decoder.with_stream(()));
+
+        if self.row_len < self.row.len() {
+            return Ok(None);
+        }
+
+        let y = self.y;
+        self.y += 1;
+        self.row_len = 0;
+
+        Ok(Some((y, &self.row)))
+    }
+
+    /// Decodes the header if enough bytes have been pushed, and returns whether it was decoded.
+    fn decode_header(&mut self) -> Result<bool, RangeDecodeError> {
+        let bytes = match self.buf.get(self.pos..(self.pos + HEADER_LEN)) {
+            Some(bytes) => bytes,
+            None => return Ok(false),
+        };
+
+        let (header, decoder) = Decoder::new_from_slice(bytes)
+            .with_options(self.options.clone())
+            .decode_header()?;
+
+        let width = usize::try_from(header.width()).map_err(|_| RangeDecodeError::TooLarge)?;
+        self.row.try_reserve_exact(width)
+            .map_err(RangeDecodeError::Alloc)?;
+        self.row.resize(width, Pixel::ZERO);
+
+        self.pos += HEADER_LEN;
+        self.header = Some(header);
+        self.decoder = Some(decoder.with_stream(()));
+
+        Ok(true)
+    }
+}
+
+impl Default for RangeDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RangeDecodeError {
+    Header(HeaderDecodeError<Infallible>),
+    TooLarge,
+    Alloc(TryReserveError),
+}
+
+impl fmt::Display for RangeDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header(err) => err.fmt(f),
+            Self::TooLarge => f.write_str("image too large"),
+            Self::Alloc(err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for RangeDecodeError {}
+
+impl From<HeaderDecodeError<Infallible>> for RangeDecodeError {
+    fn from(err: HeaderDecodeError<Infallible>) -> Self {
+        Self::Header(err)
+    }
+}
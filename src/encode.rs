@@ -0,0 +1,322 @@
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "std")]
+use crate::byte_sink::WriteByteSink;
+use crate::byte_sink::{ByteSink, VecByteSink};
+#[cfg(feature = "crc")]
+use crate::crc::CrcByteSink;
+use crate::decode::MAX_RUN;
+#[cfg(feature = "ext64")]
+use crate::ext64::Header64;
+use crate::header::{Channels, Header};
+use crate::pixel::Pixel;
+use crate::pixel_index::QoiIndex;
+#[cfg(feature = "wide_index")]
+use crate::wide_index::{self, IndexBits, WidePixelEncoder};
+
+/// Writes a header of type `H`, which is the standard QOI `Header` unless the encoder was created
+/// with `Encoder::new_ext64`.
+pub struct Encoder<S, H = Header> {
+    sink: S,
+    header: H,
+}
+
+impl Encoder<VecByteSink> {
+    pub fn new_to_vec(header: Header) -> Self {
+        Self::new(Vec::new().into(), header)
+    }
+
+    /// Encodes the given pixels as a QOI image described by `header`, returning the encoded bytes.
+    pub fn encode_to_vec(
+        header: Header,
+        pixels: &[Pixel],
+    ) -> Result<Vec<u8>, EncodeError<Infallible>> {
+        let mut encoder = Self::new_to_vec(header).encode_header()?;
+        encoder.encode_pixels(pixels)?;
+        encoder.finish().map(VecByteSink::into_inner)
+    }
+
+    /// Like `encode_to_vec`, but takes the pixels from an iterator, which is consumed lazily so the
+    /// pixels never need to be collected into a buffer.
+    pub fn encode_iter_to_vec<I>(
+        header: Header,
+        pixels: I,
+    ) -> Result<Vec<u8>, EncodeError<Infallible>>
+    where
+        I: IntoIterator<Item = Pixel>,
+    {
+        let mut encoder = Self::new_to_vec(header).encode_header()?;
+        encoder.encode_pixels_iter(pixels)?;
+        encoder.finish().map(VecByteSink::into_inner)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W> Encoder<WriteByteSink<W>>
+where
+    W: io::Write,
+{
+    pub fn new_to_writer(writer: W, header: Header) -> Self {
+        Self::new(writer.into(), header)
+    }
+}
+
+impl<S, H> Encoder<S, H> {
+    pub fn header(&self) -> &H {
+        &self.header
+    }
+
+    /// Returns the underlying sink, which will not have been written to.
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+}
+
+impl<S> Encoder<S>
+where
+    S: ByteSink,
+{
+    pub fn new(sink: S, header: Header) -> Self {
+        Self { sink, header }
+    }
+
+    /// Writes the 14-byte header, and returns a `PixelEncoder` which the image's pixels can then be
+    /// written to.
+    pub fn encode_header(mut self) -> Result<PixelEncoder<S>, EncodeError<S::IoError>> {
+        self.sink.write_n(self.header.to_bytes()).map_err(EncodeError::Io)?;
+
+        let num_pixels = u64::from(self.header.width()) * u64::from(self.header.height());
+
+        Ok(PixelEncoder::new(self.sink, num_pixels, self.header.channels()))
+    }
+
+    /// Writes the 15-byte header of the non-standard variant with an `N`-slot index described in the
+    /// `wide_index` module, and returns a `WidePixelEncoder` which the image's pixels can then be
+    /// written to. The result is NOT a valid QOI file.
+    #[cfg(feature = "wide_index")]
+    pub fn encode_header_wide<const N: usize>(
+        mut self,
+    ) -> Result<WidePixelEncoder<S, N>, EncodeError<S::IoError>> {
+        let mut bytes = self.header.to_bytes();
+        bytes[..4].copy_from_slice(&wide_index::MAGIC);
+        self.sink.write_n(bytes).map_err(EncodeError::Io)?;
+        self.sink.write_one(IndexBits::<N>::VALUE).map_err(EncodeError::Io)?;
+
+        let num_pixels = u64::from(self.header.width()) * u64::from(self.header.height());
+
+        Ok(WidePixelEncoder::new(self.sink, num_pixels, self.header.channels()))
+    }
+}
+
+#[cfg(feature = "ext64")]
+impl<S> Encoder<S, Header64>
+where
+    S: ByteSink,
+{
+    /// Creates an encoder which writes a header using the non-standard 64-bit dimension extension
+    /// described in the `ext64` module. The result is NOT a valid QOI file; use `Encoder::new` for
+    /// images whose dimensions fit in a standard header.
+    pub fn new_ext64(sink: S, header: Header64) -> Self {
+        Self { sink, header }
+    }
+
+    /// Writes the 22-byte extended header, and returns a `PixelEncoder` which the image's pixels
+    /// can then be written to.
+    pub fn encode_header(mut self) -> Result<PixelEncoder<S>, EncodeError<S::IoError>> {
+        self.sink.write_n(self.header.to_bytes()).map_err(EncodeError::Io)?;
+
+        // An image with more than `u64::MAX` pixels could never be finished anyway, so saturating
+        // makes no difference
+        let num_pixels = self.header.width().saturating_mul(self.header.height());
+
+        Ok(PixelEncoder::new(self.sink, num_pixels, self.header.channels()))
+    }
+}
+
+pub struct PixelEncoder<S> {
+    sink: S,
+    previous: Pixel,
+    index: QoiIndex,
+    remaining: u64,
+    /// The number of repeats of `previous` which have not been written yet.
+    run: u8,
+    channels: Channels,
+}
+
+impl<S> PixelEncoder<S>
+where
+    S: ByteSink,
+{
+    fn new(sink: S, num_pixels: u64, channels: Channels) -> Self {
+        Self {
+            sink,
+            previous: Pixel::BLACK,
+            index: QoiIndex::new(),
+            remaining: num_pixels,
+            run: 0,
+            channels,
+        }
+    }
+
+    /// Encodes the given pixels, which continue on from any pixels encoded previously. Returns
+    /// `EncodeError::TooManyPixels` without encoding any of them if there are more pixels than the
+    /// image has left.
+    ///
+    /// If the header says the image has no alpha channel, the alpha of every pixel is treated as
+    /// 255, as the QOI specification requires.
+    pub fn encode_pixels(&mut self, pixels: &[Pixel]) -> Result<(), EncodeError<S::IoError>> {
+        if pixels.len() as u64 > self.remaining {
+            return Err(EncodeError::TooManyPixels);
+        }
+
+        for &pixel in pixels {
+            self.encode_pixel_unchecked(pixel).map_err(EncodeError::Io)?;
+            self.remaining -= 1;
+        }
+
+        Ok(())
+    }
+
+    /// Encodes the pixels from the given iterator, which continue on from any pixels encoded
+    /// previously. If the iterator yields more pixels than the image has left, the pixels which fit
+    /// are encoded and then `EncodeError::TooManyPixels` is returned.
+    pub fn encode_pixels_iter<I>(&mut self, pixels: I) -> Result<(), EncodeError<S::IoError>>
+    where
+        I: IntoIterator<Item = Pixel>,
+    {
+        for pixel in pixels {
+            if self.remaining == 0 {
+                return Err(EncodeError::TooManyPixels);
+            }
+
+            self.encode_pixel_unchecked(pixel).map_err(EncodeError::Io)?;
+            self.remaining -= 1;
+        }
+
+        Ok(())
+    }
+
+    /// Encodes a single pixel, which continues on from any pixels encoded previously.
+    pub fn encode_pixel(&mut self, pixel: Pixel) -> Result<(), EncodeError<S::IoError>> {
+        self.encode_pixels(&[pixel])
+    }
+
+    /// Returns the number of pixels which must still be encoded to complete the image.
+    pub fn remaining_pixels(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Completes the image, writing any run of repeated pixels which is still pending followed by
+    /// the end marker, and returns the underlying sink. The output is not a valid QOI file until
+    /// this has been called. Returns `EncodeError::TooFewPixels` if fewer pixels were encoded than
+    /// the header says the image contains.
+    pub fn finish(mut self) -> Result<S, EncodeError<S::IoError>> {
+        if self.remaining > 0 {
+            return Err(EncodeError::TooFewPixels {
+                missing: self.remaining,
+            });
+        }
+
+        self.flush_run().map_err(EncodeError::Io)?;
+        self.sink.write_n(Header::END_MARKER).map_err(EncodeError::Io)?;
+
+        Ok(self.sink)
+    }
+
+    /// Writes a QOI_OP_RUN chunk for the pending run, if there is one.
+    #[inline]
+    fn flush_run(&mut self) -> Result<(), S::IoError> {
+        if self.run > 0 {
+            // The run length is stored with a bias of -1
+            self.sink.write_one(0xC0 | (self.run - 1))?;
+            self.run = 0;
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn encode_pixel_unchecked(&mut self, mut pixel: Pixel) -> Result<(), S::IoError> {
+        if self.channels == Channels::Rgb {
+            pixel.a = u8::MAX;
+        }
+
+        let previous = self.previous;
+
+        if pixel == previous {
+            self.run += 1;
+            // Longer runs would collide with the QOI_OP_RGB and QOI_OP_RGBA tags
+            if self.run == MAX_RUN {
+                self.flush_run()?;
+            }
+            return Ok(());
+        }
+
+        self.flush_run()?;
+        self.previous = pixel;
+
+        if self.index.contains(pixel) {
+            return self.sink.write_one(pixel.qoi_hash());
+        }
+
+        self.index.insert(pixel);
+
+        if let Some(chunk) = pixel.diff_chunk(previous) {
+            self.sink.write_one(chunk)
+        } else if let Some(chunk) = pixel.luma_chunk(previous) {
+            self.sink.write_n(chunk)
+        } else if pixel.a == previous.a {
+            let [r, g, b] = pixel.rgb();
+            self.sink.write_n([0xFE, r, g, b])
+        } else {
+            let [r, g, b, a] = pixel.rgba();
+            self.sink.write_n([0xFF, r, g, b, a])
+        }
+    }
+}
+
+#[cfg(feature = "crc")]
+impl<S> PixelEncoder<CrcByteSink<S>>
+where
+    S: ByteSink,
+{
+    /// Like `finish`, but also writes the CRC-32 of the file after the end marker, as described in
+    /// the `crc` module. Returns the sink the `CrcByteSink` wraps.
+    pub fn finish_with_crc(self) -> Result<S, EncodeError<S::IoError>> {
+        let sink = self.finish()?;
+        let crc = sink.crc();
+        let mut sink = sink.into_inner();
+        sink.write_n(crc.to_be_bytes()).map_err(EncodeError::Io)?;
+        Ok(sink)
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EncodeError<E> {
+    TooManyPixels,
+    TooFewPixels { missing: u64 },
+    Io(E),
+}
+
+impl<E> fmt::Display for EncodeError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyPixels => f.write_str("more pixels than the header allows"),
+            Self::TooFewPixels { missing } => write!(f, "image is missing {} pixels", missing),
+            Self::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> error::Error for EncodeError<E> where E: error::Error {}
@@ -0,0 +1,97 @@
+//! A non-standard extension to QOI which allows images with 64-bit dimensions, for very large
+//! (e.g. tiled gigapixel) images. Files using the extension are NOT valid QOI files and will be
+//! rejected by other QOI decoders.
+//!
+//! An extended file has the following 22-byte header, followed by the standard QOI chunk stream and
+//! end marker:
+//!
+//! ```text
+//! magic       [u8; 4]  "qoi8"
+//! width       u64      big-endian
+//! height      u64      big-endian
+//! channels    u8       same as QOI
+//! colorspace  u8       same as QOI
+//! ```
+//!
+//! The only difference from a standard QOI header is the magic bytes and the width of the two
+//! dimension fields. Standard `qoif` files always use 32-bit dimensions.
+//!
+//! Extended files are written by an encoder created with `Encoder::new_ext64` and read with
+//! `Decoder::decode_header_ext64`, which also accepts standard files.
+
+use crate::header::{Channels, ColSpace, Header};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Header64 {
+    width: u64,
+    height: u64,
+    channels: Channels,
+    col_space: ColSpace,
+}
+
+impl Header64 {
+    pub const MAGIC: [u8; 4] = *b"qoi8";
+
+    pub const fn new(width: u64, height: u64, channels: Channels, col_space: ColSpace) -> Self {
+        Self {
+            width,
+            height,
+            channels,
+            col_space,
+        }
+    }
+
+    pub const fn width(&self) -> u64 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u64 {
+        self.height
+    }
+
+    pub const fn dimensions(&self) -> (u64, u64) {
+        (self.width(), self.height())
+    }
+
+    pub const fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    pub const fn col_space(&self) -> ColSpace {
+        self.col_space
+    }
+
+    /// Returns the 22 bytes which begin an extended file with this header: the `qoi8` magic bytes,
+    /// the width and height as big-endian `u64`s, then the channels and colour space bytes.
+    pub fn to_bytes(&self) -> [u8; 22] {
+        let mut bytes = [0; 22];
+        bytes[..4].copy_from_slice(&Self::MAGIC);
+        bytes[4..12].copy_from_slice(&self.width.to_be_bytes());
+        bytes[12..20].copy_from_slice(&self.height.to_be_bytes());
+        bytes[20] = self.channels.into();
+        bytes[21] = self.col_space.into();
+        bytes
+    }
+
+    /// Returns the equivalent standard QOI header, or `None` if either dimension does not fit in a
+    /// `u32`.
+    pub fn to_standard(&self) -> Option<Header> {
+        Some(Header::new(
+            self.width.try_into().ok()?,
+            self.height.try_into().ok()?,
+            self.channels,
+            self.col_space,
+        ))
+    }
+}
+
+impl From<Header> for Header64 {
+    fn from(header: Header) -> Self {
+        Self::new(
+            header.width().into(),
+            header.height().into(),
+            header.channels(),
+            header.col_space(),
+        )
+    }
+}
@@ -1,9 +1,18 @@
+#[cfg(feature = "alloc")]
+use alloc::borrow::ToOwned;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+use core::fmt;
+#[cfg(feature = "alloc")]
+use core::str::FromStr;
+#[cfg(feature = "std")]
 use std::error;
-use std::fmt;
 
 use crate::hex::HexBytes;
+use crate::pixel::Pixel;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     pub(crate) width: u32,
     pub(crate) height: u32,
@@ -14,6 +23,9 @@ pub struct Header {
 impl Header {
     pub(crate) const MAGIC: [u8; 4] = *b"qoif";
 
+    /// The bytes which mark the end of the chunk stream, after the last pixel.
+    pub const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
     pub const fn new(width: u32, height: u32, channels: Channels, col_space: ColSpace) -> Self {
         Self {
             width,
@@ -43,6 +55,28 @@ impl Header {
         self.col_space
     }
 
+    /// Returns the number of bytes needed to store every pixel of the image with the header's
+    /// number of channels, such as for a buffer to pass to `PixelDecoder::decode_bytes_into` with
+    /// `Pixel::rgb` or `Pixel::rgba` as the transform. Returns `None` if the length does not fit
+    /// in a `usize`, which is possible on 32-bit targets.
+    pub fn decoded_len(&self) -> Option<usize> {
+        usize::try_from(self.width).ok()?
+            .checked_mul(usize::try_from(self.height).ok()?)?
+            .checked_mul(self.channels.num_channels())
+    }
+
+    /// Returns the 14 bytes which begin a QOI file with this header: the magic bytes, the width and
+    /// height as big-endian `u32`s, then the channels and colour space bytes.
+    pub fn to_bytes(&self) -> [u8; 14] {
+        let mut bytes = [0; 14];
+        bytes[..4].copy_from_slice(&Self::MAGIC);
+        bytes[4..8].copy_from_slice(&self.width.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.height.to_be_bytes());
+        bytes[12] = self.channels.into();
+        bytes[13] = self.col_space.into();
+        bytes
+    }
+
     pub(crate) fn validate_magic(magic: [u8; 4]) -> Result<(), MagicError> {
         if magic == Self::MAGIC {
             Ok(())
@@ -52,7 +86,21 @@ impl Header {
     }
 }
 
+/// Formats the header as its dimensions, channels and colour space, such as `640x480 RGBA sRGB`.
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} {} {}",
+            self.width, self.height, self.channels, self.col_space
+        )
+    }
+}
+
+/// With the `serde` feature, serialized as `"rgb"` or `"rgba"`.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Channels {
     Rgb,
     Rgba,
@@ -61,6 +109,29 @@ pub enum Channels {
 impl Channels {
     const RGB_BYTE: u8 = 0x03;
     const RGBA_BYTE: u8 = 0x04;
+
+    /// Returns the number of channels each pixel has: 3 for `Rgb` or 4 for `Rgba`. This is the same
+    /// number as the byte used for the channels in the header, but only because the spec happens
+    /// to encode them that way, so use this rather than converting to `u8` when computing strides
+    /// and buffer sizes.
+    pub fn num_channels(self) -> usize {
+        match self {
+            Channels::Rgb => 3,
+            Channels::Rgba => 4,
+        }
+    }
+
+    /// Returns `Rgba` if any of the given pixels is not fully opaque, or `Rgb` otherwise, including
+    /// when there are no pixels. Encoding an opaque image as `Rgb` means decoders can skip the
+    /// alpha channel. This is a full pass over the pixels, so it is worth avoiding if the channels
+    /// are already known.
+    pub fn detect(pixels: &[Pixel]) -> Self {
+        if pixels.iter().all(|pixel| pixel.a == u8::MAX) {
+            Self::Rgb
+        } else {
+            Self::Rgba
+        }
+    }
 }
 
 impl TryFrom<u8> for Channels {
@@ -84,7 +155,35 @@ impl From<Channels> for u8 {
     }
 }
 
+impl fmt::Display for Channels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rgb => f.write_str("RGB"),
+            Self::Rgba => f.write_str("RGBA"),
+        }
+    }
+}
+
+/// Parses `"rgb"` or `"rgba"`, ignoring case.
+#[cfg(feature = "alloc")]
+impl FromStr for Channels {
+    type Err = ParseChannelsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("rgb") {
+            Ok(Self::Rgb)
+        } else if s.eq_ignore_ascii_case("rgba") {
+            Ok(Self::Rgba)
+        } else {
+            Err(ParseChannelsError { bad_value: s.to_owned() })
+        }
+    }
+}
+
+/// With the `serde` feature, serialized as `"srgb"` or `"linear"`.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum ColSpace {
     Srgb,
     Linear,
@@ -116,7 +215,32 @@ impl From<ColSpace> for u8 {
     }
 }
 
-#[derive(Debug)]
+impl fmt::Display for ColSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Srgb => f.write_str("sRGB"),
+            Self::Linear => f.write_str("linear"),
+        }
+    }
+}
+
+/// Parses `"srgb"` or `"linear"`, ignoring case.
+#[cfg(feature = "alloc")]
+impl FromStr for ColSpace {
+    type Err = ParseColSpaceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("srgb") {
+            Ok(Self::Srgb)
+        } else if s.eq_ignore_ascii_case("linear") {
+            Ok(Self::Linear)
+        } else {
+            Err(ParseColSpaceError { bad_value: s.to_owned() })
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct MagicError {
     bad_value: [u8; 4],
 }
@@ -127,6 +251,19 @@ impl MagicError {
     }
 }
 
+impl MagicError {
+    /// Returns a guess at what the input actually is, based on the bad magic bytes, for the most
+    /// common ways of passing the wrong data to the decoder.
+    fn hint(&self) -> Option<&'static str> {
+        match self.bad_value {
+            [0x00, 0x00, 0x00, 0x00] => Some("input appears to be empty or zeroed"),
+            [0x89, b'P', b'N', b'G'] => Some("input appears to be a PNG file"),
+            [0xFF, 0xD8, 0xFF, _] => Some("input appears to be a JPEG file"),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for MagicError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -134,13 +271,20 @@ impl fmt::Display for MagicError {
             "bad magic bytes {}, expected {}",
             HexBytes::new(&self.bad_value),
             HexBytes::new(&Header::MAGIC)
-        )
+        )?;
+
+        if let Some(hint) = self.hint() {
+            write!(f, " ({})", hint)?;
+        }
+
+        Ok(())
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for MagicError {}
 
-#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct ChannelsError {
     bad_value: u8,
 }
@@ -163,9 +307,10 @@ impl fmt::Display for ChannelsError {
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for ChannelsError {}
 
-#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct ColSpaceError {
     bad_value: u8,
 }
@@ -188,4 +333,59 @@ impl fmt::Display for ColSpaceError {
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for ColSpaceError {}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct ParseChannelsError {
+    bad_value: String,
+}
+
+#[cfg(feature = "alloc")]
+impl ParseChannelsError {
+    pub fn bad_value(&self) -> &str {
+        &self.bad_value
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for ParseChannelsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid channels {:?}, expected \"rgb\" or \"rgba\"",
+            self.bad_value
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for ParseChannelsError {}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct ParseColSpaceError {
+    bad_value: String,
+}
+
+#[cfg(feature = "alloc")]
+impl ParseColSpaceError {
+    pub fn bad_value(&self) -> &str {
+        &self.bad_value
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for ParseColSpaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid color space {:?}, expected \"srgb\" or \"linear\"",
+            self.bad_value
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for ParseColSpaceError {}
@@ -1,4 +1,4 @@
-use std::fmt;
+use core::fmt;
 
 pub(crate) struct HexBytes<'a> {
     bytes: &'a [u8],
@@ -22,8 +22,7 @@ impl<'a> fmt::Display for HexBytes<'a> {
             .iter()
             .copied()
             .map(byte_to_hex)
-            .map(|(h1, h2)| write!(f, "{}{}", h1, h2))
-            .collect()
+            .try_for_each(|(h1, h2)| write!(f, "{}{}", h1, h2))
     }
 }
 
@@ -35,6 +34,6 @@ fn nibble_to_hex(nibble: u8) -> char {
     (nibble
         + match nibble {
             0..=9 => 0x30,
-            _ => 0x41,
+            _ => 0x41 - 10,
         }) as char
 }
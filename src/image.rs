@@ -0,0 +1,116 @@
+//! Support for decoding QOI images with the `image` crate, so that they can be loaded into a
+//! `DynamicImage` like any other format it supports, and for encoding a `DynamicImage` as QOI.
+
+use std::convert::Infallible;
+use std::error;
+use std::io::{self, Cursor};
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{ColorType, DynamicImage, GenericImageView, ImageDecoder, ImageError, ImageResult};
+
+use crate::byte_stream::{ByteStream, ReadByteStream};
+use crate::decode::{Decoder, PixelDecoder};
+use crate::encode::{EncodeError, Encoder};
+use crate::header::{Channels, ColSpace, Header};
+use crate::pixel::Pixel;
+
+/// A QOI decoder which implements `image::ImageDecoder`. Pixels are decoded as RGB8 or RGBA8
+/// depending on the channels the header declares.
+///
+/// `read_image`, which `image::DynamicImage::from_decoder` uses, decodes straight into the buffer
+/// it is given without any intermediate copy. `into_reader` has to decode the whole image into a
+/// vec first.
+pub struct QoiDecoder<S> {
+    header: Header,
+    decoder: PixelDecoder<S>,
+}
+
+impl<R> QoiDecoder<ReadByteStream<R>>
+where
+    R: io::Read,
+{
+    pub fn from_reader(reader: R) -> ImageResult<Self> {
+        Self::new(Decoder::new_from_reader(reader))
+    }
+}
+
+impl<S> QoiDecoder<S>
+where
+    S: ByteStream,
+    S::IoError: error::Error + Send + Sync + 'static,
+{
+    /// Decodes the header using the given decoder, so that the image's dimensions and colour type
+    /// are known.
+    pub fn new(decoder: Decoder<S>) -> ImageResult<Self> {
+        let (header, decoder) = decoder.decode_header().map_err(decoding_error)?;
+        Ok(Self { header, decoder })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+}
+
+impl<'a, S> ImageDecoder<'a> for QoiDecoder<S>
+where
+    S: ByteStream + 'a,
+    S::IoError: error::Error + Send + Sync + 'static,
+{
+    type Reader = Cursor<Vec<u8>>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.header.dimensions()
+    }
+
+    fn color_type(&self) -> ColorType {
+        match self.header.channels() {
+            Channels::Rgb => ColorType::Rgb8,
+            Channels::Rgba => ColorType::Rgba8,
+        }
+    }
+
+    fn into_reader(self) -> ImageResult<Self::Reader> {
+        let (buf, _) = self.decoder
+            .decode_native_bytes_vec(&self.header)
+            .map_err(decoding_error)?;
+        Ok(Cursor::new(buf))
+    }
+
+    fn read_image(mut self, buf: &mut [u8]) -> ImageResult<()> {
+        assert_eq!(buf.len() as u64, self.total_bytes());
+
+        let decoded = match self.header.channels() {
+            Channels::Rgb => self.decoder.decode_bytes_into(buf, Pixel::rgb),
+            Channels::Rgba => self.decoder.decode_bytes_into(buf, Pixel::rgba),
+        };
+
+        decoded.map(|_| ()).map_err(decoding_error)
+    }
+}
+
+fn decoding_error<E>(err: E) -> ImageError
+where
+    E: error::Error + Send + Sync + 'static,
+{
+    ImageError::Decoding(DecodingError::new(ImageFormatHint::Name("QOI".to_owned()), err))
+}
+
+/// Encodes the given image as QOI, returning the encoded bytes. The image is encoded with an alpha
+/// channel if its colour type has one, and without otherwise; other bit depths are converted to 8
+/// bits per channel first.
+pub fn encode_dynamic_image(
+    image: &DynamicImage,
+    col_space: ColSpace,
+) -> Result<Vec<u8>, EncodeError<Infallible>> {
+    let (width, height) = image.dimensions();
+
+    if image.color().has_alpha() {
+        let header = Header::new(width, height, Channels::Rgba, col_space);
+        let pixels = image.to_rgba8();
+        Encoder::encode_iter_to_vec(header, pixels.pixels().map(|pixel| Pixel::from(pixel.0)))
+    } else {
+        let header = Header::new(width, height, Channels::Rgb, col_space);
+        let pixels = image.to_rgb8();
+        Encoder::encode_iter_to_vec(header, pixels.pixels().map(|pixel| Pixel::from(pixel.0)))
+    }
+}
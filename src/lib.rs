@@ -1,16 +1,58 @@
 // TODO
 // [x] Decode
-// [ ] Encode
-// [ ] Image viewer
-// [ ] no_std
+// [x] Encode
+// [x] Image viewer
+// [x] no_std
 
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "safe_only", forbid(unsafe_code))]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(all(feature = "memmap", feature = "safe_only"))]
+compile_error!("the `memmap` feature cannot be used with `safe_only`, since mapping a file is unsafe");
+
+// Modules which need `std` are gated on it as well as on their own feature, so that these errors
+// are reported on their own rather than alongside errors from the code the feature would enable
+#[cfg(all(feature = "image", not(feature = "std")))]
+compile_error!("the `image` feature requires the `std` feature");
+
+#[cfg(all(feature = "rayon", not(feature = "std")))]
+compile_error!("the `rayon` feature requires the `std` feature");
+
+#[cfg(all(feature = "tokio", not(feature = "std")))]
+compile_error!("the `tokio` feature requires the `std` feature");
+
+#[cfg(feature = "aligned")]
+pub mod aligned;
+pub mod byte_sink;
 pub mod byte_stream;
+#[cfg(feature = "crc")]
+pub mod crc;
 pub mod decode;
+#[cfg(feature = "alloc")]
+pub mod encode;
+#[cfg(feature = "ext64")]
+pub mod ext64;
 pub mod header;
 mod hex;
+#[cfg(all(feature = "image", feature = "std"))]
+pub mod image;
+#[cfg(all(feature = "rayon", feature = "std"))]
+pub mod parallel;
 pub mod pixel;
 mod pixel_index;
+mod srgb;
+#[cfg(feature = "alloc")]
+pub mod validate;
+#[cfg(feature = "wide_index")]
+pub mod wide_index;
 
-pub use decode::Decoder;
+pub use decode::{pixels, Decoder, DecoderOptions};
+#[cfg(feature = "alloc")]
+pub use encode::Encoder;
 pub use header::Header;
+#[cfg(all(feature = "image", feature = "std"))]
+pub use self::image::encode_dynamic_image;
 pub use pixel::Pixel;
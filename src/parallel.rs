@@ -0,0 +1,66 @@
+//! Decoding several independent QOI images at once on rayon's thread pool.
+//!
+//! QOI has to be decoded sequentially, since every chunk depends on the pixels before it, so a
+//! single image can only ever use one core. Producers which want parallel decoding can instead split
+//! an image into tiles and encode each tile as a separate, complete QOI file.
+
+use std::convert::Infallible;
+use std::error;
+use std::fmt;
+
+use rayon::prelude::*;
+
+use crate::decode::{DecodeAllError, Decoder, HeaderDecodeError};
+use crate::pixel::Pixel;
+
+/// Decodes each of the given slices, each of which must be a complete QOI file with its own header,
+/// in parallel. The decoded pixels are returned in the same order as the slices.
+///
+/// If any of the slices fails to decode, the error for the first of them is returned, although the
+/// others will still have been decoded.
+pub fn decode_tiles_parallel(streams: &[&[u8]]) -> Result<Vec<Vec<Pixel>>, TileDecodeError> {
+    let tiles = streams
+        .par_iter()
+        .enumerate()
+        .map(|(tile, bytes)| decode_tile(tile, bytes))
+        .collect::<Vec<_>>();
+
+    tiles.into_iter().collect()
+}
+
+fn decode_tile(tile: usize, bytes: &[u8]) -> Result<Vec<Pixel>, TileDecodeError> {
+    let (_header, decoder) = Decoder::new_from_slice(bytes)
+        .decode_header()
+        .map_err(|err| TileDecodeError::Header { tile, err })?;
+
+    decoder
+        .decode_pixels_vec()
+        .map_err(|err| TileDecodeError::Pixels { tile, err })
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TileDecodeError {
+    Header { tile: usize, err: HeaderDecodeError<Infallible> },
+    Pixels { tile: usize, err: DecodeAllError<Infallible> },
+}
+
+impl TileDecodeError {
+    /// Returns the position of the tile which failed to decode in the slice of tiles.
+    pub fn tile(&self) -> usize {
+        match self {
+            Self::Header { tile, .. } | Self::Pixels { tile, .. } => *tile,
+        }
+    }
+}
+
+impl fmt::Display for TileDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header { tile, err } => write!(f, "tile {}: {}", tile, err),
+            Self::Pixels { tile, err } => write!(f, "tile {}: {}", tile, err),
+        }
+    }
+}
+
+impl error::Error for TileDecodeError {}
@@ -1,4 +1,13 @@
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::pixel_index::QoiIndex;
+use crate::srgb::{LINEAR_TO_SRGB, SRGB_TO_LINEAR};
+
+/// With the `serde` feature, a pixel is serialized as a struct with the fields `r`, `g`, `b` and
+/// `a`, and can be deserialized from either that or a sequence of four bytes.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pixel {
     pub r: u8,
     pub g: u8,
@@ -29,4 +38,367 @@ impl Pixel {
     pub const fn rgb(self) -> [u8; 3] {
         [self.r, self.g, self.b]
     }
+
+    #[inline]
+    pub const fn bgra(self) -> [u8; 4] {
+        [self.b, self.g, self.r, self.a]
+    }
+
+    #[inline]
+    pub const fn bgr(self) -> [u8; 3] {
+        [self.b, self.g, self.r]
+    }
+
+    /// Packs the pixel into a `u32` with red in the most significant byte, then green, blue, and alpha
+    /// in the least significant byte: `0xRRGGBBAA`. This is `rgba()` read as a big-endian integer.
+    #[inline]
+    pub const fn to_rgba_u32(self) -> u32 {
+        u32::from_be_bytes(self.rgba())
+    }
+
+    /// Unpacks a pixel from a `u32` laid out as `0xRRGGBBAA`; the inverse of `to_rgba_u32`.
+    #[inline]
+    pub const fn from_rgba_u32(rgba: u32) -> Self {
+        let [r, g, b, a] = rgba.to_be_bytes();
+        Self::new(r, g, b, a)
+    }
+
+    /// Packs the pixel into a `u32` with alpha in the most significant byte, then red, green, and
+    /// blue in the least significant byte: `0xAARRGGBB`. This is `argb()` read as a big-endian
+    /// integer.
+    #[inline]
+    pub const fn to_argb_u32(self) -> u32 {
+        u32::from_be_bytes(self.argb())
+    }
+
+    /// Unpacks a pixel from a `u32` laid out as `0xAARRGGBB`; the inverse of `to_argb_u32`.
+    #[inline]
+    pub const fn from_argb_u32(argb: u32) -> Self {
+        let [a, r, g, b] = argb.to_be_bytes();
+        Self::new(r, g, b, a)
+    }
+
+    /// Returns true if the red, green and blue channels of the two pixels are equal, regardless of
+    /// their alpha.
+    #[inline]
+    pub const fn rgb_eq(self, other: Pixel) -> bool {
+        self.r == other.r && self.g == other.g && self.b == other.b
+    }
+
+    /// Returns the slot of the 64-entry QOI index which this pixel is stored in, which is
+    /// `(r * 3 + g * 5 + b * 7 + a * 11) % 64`. This is also the QOI_OP_INDEX chunk which refers to
+    /// the pixel.
+    #[inline]
+    pub fn qoi_hash(self) -> u8 {
+        QoiIndex::pixel_hash(self) as u8
+    }
+
+    /// Packs the pixel into 16-bit RGB565, discarding alpha. Red occupies the top 5 bits, green the
+    /// middle 6 bits and blue the bottom 5 bits. Each channel is scaled to its bit width with rounding
+    /// to the nearest value rather than truncation. The packed value is returned big-endian (high byte
+    /// first), which is the byte order most SPI TFT display controllers expect.
+    #[inline]
+    pub const fn rgb565(self) -> [u8; 2] {
+        let r = scale_channel(self.r, 0x1F);
+        let g = scale_channel(self.g, 0x3F);
+        let b = scale_channel(self.b, 0x1F);
+        ((r << 11) | (g << 5) | b).to_be_bytes()
+    }
+
+    /// Packs the pixel into 16-bit RGBA4444. Red occupies the top 4 bits, followed by green, blue and
+    /// finally alpha in the bottom 4 bits. Each channel is scaled to 4 bits with rounding to the
+    /// nearest value. The packed value is returned big-endian (high byte first).
+    #[inline]
+    pub const fn rgba4444(self) -> [u8; 2] {
+        let r = scale_channel(self.r, 0xF);
+        let g = scale_channel(self.g, 0xF);
+        let b = scale_channel(self.b, 0xF);
+        let a = scale_channel(self.a, 0xF);
+        ((r << 12) | (g << 8) | (b << 4) | a).to_be_bytes()
+    }
+
+    /// Converts the red, green and blue channels from the sRGB transfer function to linear intensity,
+    /// leaving alpha unchanged. The result is rounded to 8 bits per channel, so dark sRGB values lose
+    /// precision (several of them map to the same linear value) and converting back with
+    /// `linear_to_srgb` does not always give the original pixel.
+    #[inline]
+    pub const fn srgb_to_linear(self) -> Self {
+        Self::new(
+            SRGB_TO_LINEAR[self.r as usize],
+            SRGB_TO_LINEAR[self.g as usize],
+            SRGB_TO_LINEAR[self.b as usize],
+            self.a,
+        )
+    }
+
+    /// Converts the red, green and blue channels from linear intensity to the sRGB transfer function,
+    /// leaving alpha unchanged. The result is rounded to 8 bits per channel.
+    #[inline]
+    pub const fn linear_to_srgb(self) -> Self {
+        Self::new(
+            LINEAR_TO_SRGB[self.r as usize],
+            LINEAR_TO_SRGB[self.g as usize],
+            LINEAR_TO_SRGB[self.b as usize],
+            self.a,
+        )
+    }
+
+    /// Multiplies the red, green and blue channels by the alpha, treated as a fraction of 255, for
+    /// compositing pipelines which expect premultiplied alpha. Each channel is computed as
+    /// `(c * a + 127) / 255`, which is `c * a / 255` rounded to the nearest integer.
+    #[inline]
+    pub const fn premultiplied(self) -> Self {
+        Self::new(
+            premultiply_channel(self.r, self.a),
+            premultiply_channel(self.g, self.a),
+            premultiply_channel(self.b, self.a),
+            self.a,
+        )
+    }
+
+    /// The inverse of `premultiplied`, dividing the red, green and blue channels by the alpha with
+    /// rounding to the nearest integer, and clamping to 255 for pixels which were not validly
+    /// premultiplied. A fully transparent pixel is returned unchanged, since its colour cannot be
+    /// recovered. Premultiplying discards precision, so this does not always give back the
+    /// original pixel.
+    #[inline]
+    pub const fn unpremultiplied(self) -> Self {
+        if self.a == 0 {
+            return self;
+        }
+
+        Self::new(
+            unpremultiply_channel(self.r, self.a),
+            unpremultiply_channel(self.g, self.a),
+            unpremultiply_channel(self.b, self.a),
+            self.a,
+        )
+    }
+
+    /// Composites this pixel over `background` with the Porter-Duff "over" operator, where both
+    /// pixels have straight (not premultiplied) alpha. The result is opaque whenever the background
+    /// is, so this can be used to draw a transparent image over a checkerboard. A fully transparent
+    /// pixel returns the background unchanged, and a fully opaque pixel returns itself. Channels are
+    /// computed with integer arithmetic and rounded to the nearest value.
+    #[inline]
+    pub const fn over(self, background: Pixel) -> Self {
+        match self.a {
+            0 => return background,
+            u8::MAX => return self,
+            _ => (),
+        }
+
+        // The source and background weights, scaled by 255 * 255
+        let src_weight = self.a as u32 * 255;
+        let bg_weight = background.a as u32 * (255 - self.a as u32);
+        // Non-zero, because the source alpha is non-zero
+        let total_weight = src_weight + bg_weight;
+
+        Self::new(
+            composite_channel(self.r, background.r, src_weight, bg_weight),
+            composite_channel(self.g, background.g, src_weight, bg_weight),
+            composite_channel(self.b, background.b, src_weight, bg_weight),
+            ((total_weight + 127) / 255) as u8,
+        )
+    }
+
+    /// Returns the brightness of the pixel as a weighted sum of its red, green and blue channels,
+    /// using the Rec. 709 coefficients 0.2126, 0.7152 and 0.0722, rounded to the nearest integer.
+    /// Alpha is ignored. The weights are applied to the channel values as they are, so for sRGB
+    /// images this is the gamma-encoded luma rather than linear luminance; use `srgb_to_linear`
+    /// first if linear luminance is needed.
+    ///
+    /// The coefficients are 16-bit fixed-point (13933, 46871 and 4732 out of 65536), which sum to
+    /// exactly 1 so that white maps to 255.
+    #[inline]
+    pub const fn luminance(self) -> u8 {
+        let sum = 13933 * self.r as u32 + 46871 * self.g as u32 + 4732 * self.b as u32;
+        ((sum + (1 << 15)) >> 16) as u8
+    }
+
+    /// Returns a grey pixel whose red, green and blue channels are all this pixel's `luminance`,
+    /// keeping its alpha.
+    #[inline]
+    pub const fn to_grayscale(self) -> Self {
+        let y = self.luminance();
+        Self::new(y, y, y, self.a)
+    }
+
+    /// Applies `f` to the red, green and blue channels, keeping the alpha as it is. For example,
+    /// `pixel.map_rgb(|c| 255 - c)` inverts the colour of a pixel.
+    #[inline]
+    pub fn map_rgb<F>(self, mut f: F) -> Self
+    where
+        F: FnMut(u8) -> u8,
+    {
+        Self::new(f(self.r), f(self.g), f(self.b), self.a)
+    }
+
+    /// Applies `f` to all four channels, including alpha.
+    #[inline]
+    pub fn map_all<F>(self, f: F) -> Self
+    where
+        F: FnMut(u8) -> u8,
+    {
+        self.rgba().map(f).into()
+    }
+
+    /// Returns the red, green, blue and alpha channels as floats in the range `0.0..=1.0`, by dividing
+    /// each channel by 255. No transfer function is applied, so sRGB values stay sRGB; use
+    /// `srgb_to_linear` first if linear intensities are needed.
+    #[inline]
+    pub fn to_f32_normalized(self) -> [f32; 4] {
+        self.rgba().map(|c| c as f32 / 255.0)
+    }
+
+    /// Creates a pixel from red, green, blue and alpha floats in the range `0.0..=1.0`, multiplying
+    /// each by 255 and rounding to the nearest integer. Values outside of the range are clamped to 0
+    /// or 255, and NaN becomes 0.
+    #[inline]
+    pub fn from_f32_normalized(rgba: [f32; 4]) -> Self {
+        // `f32::round` needs `std`, but the clamped value is never negative, so adding 0.5 and
+        // truncating rounds it the same way. Casting NaN to an integer gives 0
+        let [r, g, b, a] = rgba.map(|c| ((c * 255.0).clamp(0.0, 255.0) + 0.5) as u8);
+        Self::new(r, g, b, a)
+    }
+
+    /// Assembles pixels from separate red, green, blue and alpha planes, where the `i`th pixel takes
+    /// the `i`th byte of each plane. Returns `None` if the planes are not all the same length.
+    #[cfg(feature = "alloc")]
+    pub fn from_planes(r: &[u8], g: &[u8], b: &[u8], a: &[u8]) -> Option<Vec<Self>> {
+        if g.len() != r.len() || b.len() != r.len() || a.len() != r.len() {
+            return None;
+        }
+
+        Some(
+            r.iter()
+                .zip(g)
+                .zip(b)
+                .zip(a)
+                .map(|(((&r, &g), &b), &a)| Self::new(r, g, b, a))
+                .collect(),
+        )
+    }
+
+    /// Assembles opaque pixels from separate red, green and blue planes, setting the alpha of every
+    /// pixel to 255. Returns `None` if the planes are not all the same length.
+    #[cfg(feature = "alloc")]
+    pub fn from_rgb_planes(r: &[u8], g: &[u8], b: &[u8]) -> Option<Vec<Self>> {
+        if g.len() != r.len() || b.len() != r.len() {
+            return None;
+        }
+
+        Some(
+            r.iter()
+                .zip(g)
+                .zip(b)
+                .map(|((&r, &g), &b)| Self::new(r, g, b, u8::MAX))
+                .collect(),
+        )
+    }
+}
+
+impl Default for Pixel {
+    /// Returns `Pixel::ZERO`, the fully transparent black pixel which every slot of the index
+    /// starts as. Note that this is not the pixel the decoder starts with, which is `Pixel::BLACK`.
+    #[inline]
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl From<[u8; 4]> for Pixel {
+    /// Creates a pixel from its red, green, blue and alpha channels, in that order.
+    #[inline]
+    fn from([r, g, b, a]: [u8; 4]) -> Self {
+        Self::new(r, g, b, a)
+    }
+}
+
+impl From<[u8; 3]> for Pixel {
+    /// Creates an opaque pixel from its red, green and blue channels, in that order.
+    #[inline]
+    fn from([r, g, b]: [u8; 3]) -> Self {
+        Self::new(r, g, b, u8::MAX)
+    }
+}
+
+impl From<Pixel> for [u8; 4] {
+    #[inline]
+    fn from(pixel: Pixel) -> Self {
+        pixel.rgba()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Pixel {
+    /// Returns the QOI_OP_DIFF chunk which changes `previous` into this pixel, or `None` if the alpha
+    /// differs or any of the red, green and blue differences is outside of `-2..=1`. Differences
+    /// wrap around, so 255 to 0 is a difference of 1.
+    #[inline]
+    pub(crate) fn diff_chunk(self, previous: Pixel) -> Option<u8> {
+        if self.a != previous.a {
+            return None;
+        }
+
+        let dr = self.r.wrapping_sub(previous.r).wrapping_add(2);
+        let dg = self.g.wrapping_sub(previous.g).wrapping_add(2);
+        let db = self.b.wrapping_sub(previous.b).wrapping_add(2);
+
+        if dr | dg | db < 4 {
+            Some(0x40 | (dr << 4) | (dg << 2) | db)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the QOI_OP_LUMA chunk which changes `previous` into this pixel, or `None` if the
+    /// alpha differs, the green difference is outside of `-32..=31`, or the red or blue difference
+    /// minus the green difference is outside of `-8..=7`. Differences wrap around.
+    #[inline]
+    pub(crate) fn luma_chunk(self, previous: Pixel) -> Option<[u8; 2]> {
+        if self.a != previous.a {
+            return None;
+        }
+
+        let dg = self.g.wrapping_sub(previous.g);
+        let dr_dg = self.r.wrapping_sub(previous.r).wrapping_sub(dg).wrapping_add(8);
+        let db_dg = self.b.wrapping_sub(previous.b).wrapping_sub(dg).wrapping_add(8);
+        let dg = dg.wrapping_add(32);
+
+        if dg < 64 && dr_dg | db_dg < 16 {
+            Some([0x80 | dg, (dr_dg << 4) | db_dg])
+        } else {
+            None
+        }
+    }
+}
+
+#[inline]
+const fn premultiply_channel(c: u8, a: u8) -> u8 {
+    ((c as u16 * a as u16 + 127) / 255) as u8
+}
+
+/// Returns the weighted average of `src` and `bg`. The weights must not both be zero.
+#[inline]
+const fn composite_channel(src: u8, bg: u8, src_weight: u32, bg_weight: u32) -> u8 {
+    let total_weight = src_weight + bg_weight;
+    ((src as u32 * src_weight + bg as u32 * bg_weight + total_weight / 2) / total_weight) as u8
+}
+
+/// `a` must not be zero.
+#[inline]
+const fn unpremultiply_channel(c: u8, a: u8) -> u8 {
+    let c = (c as u16 * 255 + a as u16 / 2) / a as u16;
+    if c > u8::MAX as u16 {
+        u8::MAX
+    } else {
+        c as u8
+    }
+}
+
+/// Scales an 8-bit channel value to the range `0..=max`, rounding to the nearest value.
+#[inline]
+const fn scale_channel(c: u8, max: u16) -> u16 {
+    (c as u16 * max + 127) / 255
 }
@@ -1,26 +1,58 @@
 use crate::pixel::Pixel;
 
-pub(crate) struct PixelIndex {
-    inner: [Pixel; Self::SIZE],
+/// The number of slots in the index used by standard QOI.
+pub(crate) const QOI_INDEX_SIZE: usize = 64;
+
+/// The index used by standard QOI.
+pub(crate) type QoiIndex = PixelIndex<QOI_INDEX_SIZE>;
+
+/// An index of previously-seen pixels with `SIZE` slots, which must be a power of two. Standard
+/// QOI always uses `QOI_INDEX_SIZE` slots, since a QOI_OP_INDEX chunk can only refer to 64 slots;
+/// other sizes are used by the non-standard variant in the `wide_index` module.
+#[derive(Clone)]
+pub(crate) struct PixelIndex<const SIZE: usize> {
+    inner: [Pixel; SIZE],
 }
 
-impl PixelIndex {
-    const SIZE: usize = 64;
+impl<const SIZE: usize> PixelIndex<SIZE> {
+    /// Mask which reduces any value to a valid slot. Evaluating it fails to compile if `SIZE` is not
+    /// a power of two.
+    const MASK: usize = {
+        assert!(SIZE.is_power_of_two(), "index size must be a power of two");
+        SIZE - 1
+    };
 
-    /// Creates a new zero-initialised index
+    /// Creates a new zero-initialised index. The index is stored inline rather than on the heap, so
+    /// for the standard size this is just a 256-byte memset; reusing an existing index would need
+    /// to clear it anyway, so there is nothing to be gained by doing so.
     pub fn new() -> Self {
         Self {
-            inner: [Pixel::ZERO; Self::SIZE],
+            inner: [Pixel::ZERO; SIZE],
         }
     }
 
-    /// Gets the pixel from the index referred to by the given QOI_OP_INDEX chunk
-    pub fn masked_get(&self, chunk: u8) -> Pixel {
+    /// Gets the pixel from the index referred to by the low bits of `slot`. For the standard index
+    /// size, `slot` can be a QOI_OP_INDEX chunk, since the mask removes its tag bits.
+    #[cfg(not(feature = "safe_only"))]
+    pub fn masked_get(&self, slot: usize) -> Pixel {
         // SAFETY:
-        // Masking chunk by `Self::SIZE - 1` (0b00111111) limits it to 6 bits, so it can never
-        // exceed 63. Since the length of the array is 64, this means that it can never be
-        // out-of-bounds
-        unsafe { *self.inner.get_unchecked((chunk & (Self::SIZE as u8 - 1)) as usize) }
+        // `SIZE` is a power of two, so masking by `SIZE - 1` limits `slot` to at most `SIZE - 1`.
+        // Since the length of the array is `SIZE`, this means that it can never be out-of-bounds
+        unsafe { *self.inner.get_unchecked(slot & Self::MASK) }
+    }
+
+    /// Gets the pixel from the index referred to by the low bits of `slot`. For the standard index
+    /// size, `slot` can be a QOI_OP_INDEX chunk, since the mask removes its tag bits.
+    #[cfg(feature = "safe_only")]
+    pub fn masked_get(&self, slot: usize) -> Pixel {
+        self.inner[slot & Self::MASK]
+    }
+
+    /// Returns true if the given pixel is stored in the index, at the position corresponding to its
+    /// hash value.
+    #[cfg(feature = "alloc")]
+    pub fn contains(&self, pixel: Pixel) -> bool {
+        self.inner[Self::pixel_hash(pixel)] == pixel
     }
 
     /// Inserts the given pixel into the index at the position corresponding to its hash value
@@ -28,9 +60,12 @@ impl PixelIndex {
         self.inner[Self::pixel_hash(pixel)] = pixel;
     }
 
+    /// Returns the slot the given pixel is stored in. For the standard index size, this is also the
+    /// QOI_OP_INDEX chunk which refers to the slot.
     #[inline(always)]
-    fn pixel_hash(pixel: Pixel) -> usize {
+    pub fn pixel_hash(pixel: Pixel) -> usize {
+        // Equivalent to `% SIZE`, since `SIZE` is a power of two
         (pixel.r as usize * 3 + pixel.g as usize * 5 + pixel.b as usize * 7 + pixel.a as usize * 11)
-            % Self::SIZE
+            & Self::MASK
     }
 }
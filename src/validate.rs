@@ -0,0 +1,103 @@
+//! Reports of everything wrong with a QOI file, produced by `Decoder::decode_validated`. Unlike the
+//! errors returned by the other decoding methods, which stop at the first problem, a report lists
+//! every problem found in the file.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::hex::HexBytes;
+use crate::header::{self, Header};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ValidationReport {
+    header: Option<Header>,
+    issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub(crate) fn new(header: Option<Header>, issues: Vec<ValidationIssue>) -> Self {
+        Self { header, issues }
+    }
+
+    /// Returns the file's header, or `None` if the header was truncated or any of its fields were
+    /// invalid.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Returns every problem found with the file, in the order they appear in the file.
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+
+    /// Returns true if no problems were found with the file.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// A single problem found with a QOI file. Offsets are in bytes from the start of the file.
+#[derive(Clone, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// The file ended before the end of the 14-byte header. Nothing after this is checked.
+    TruncatedHeader,
+    Magic(header::MagicError),
+    Channels(header::ChannelsError),
+    ColSpace(header::ColSpaceError),
+    /// The image has more pixels than the decoder's options allow, or than fit in a `u64`. The
+    /// pixel data is not checked, to avoid doing work on behalf of an oversized image.
+    TooLarge { megapixels: f64 },
+    /// The file ended partway through the pixel data.
+    TruncatedPixels { offset: u64, expected: u64, found: u64 },
+    /// The last QOI_OP_RUN chunk continued past the last pixel of the image.
+    RunPastEnd { offset: u64, excess: u64 },
+    /// The bytes after the last pixel were not the end marker. `found` holds the bytes which were
+    /// there instead, and is shorter than the end marker if the file ended first.
+    BadEndMarker { offset: u64, found: Vec<u8> },
+    /// There were bytes after the end marker.
+    TrailingBytes { offset: u64, len: u64 },
+    /// Some chunks were not the chunks a canonical encoder (one which makes the same choices as
+    /// the reference encoder) would have produced. Only the first such chunk is reported, along
+    /// with the total number of them.
+    NonCanonical { offset: u64, pixel: u64, count: u64 },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TruncatedHeader => f.write_str("header truncated"),
+            Self::Magic(err) => err.fmt(f),
+            Self::Channels(err) => err.fmt(f),
+            Self::ColSpace(err) => err.fmt(f),
+            Self::TooLarge { megapixels } => {
+                write!(f, "image too large ({:.2} megapixels)", megapixels)
+            }
+            Self::TruncatedPixels { offset, expected, found } => write!(
+                f,
+                "pixel data truncated at offset {}, found {} of {} pixels",
+                offset, found, expected
+            ),
+            Self::RunPastEnd { offset, excess } => write!(
+                f,
+                "run at offset {} continues {} pixels past the end of the image",
+                offset, excess
+            ),
+            Self::BadEndMarker { offset, found } => write!(
+                f,
+                "bad end marker {} at offset {}, expected {}",
+                HexBytes::new(found),
+                offset,
+                HexBytes::new(&Header::END_MARKER)
+            ),
+            Self::TrailingBytes { offset, len } => {
+                write!(f, "{} trailing bytes after the end marker at offset {}", len, offset)
+            }
+            Self::NonCanonical { offset, pixel, count } => write!(
+                f,
+                "{} non-canonical chunks, the first at offset {} (pixel {})",
+                count, offset, pixel
+            ),
+        }
+    }
+}
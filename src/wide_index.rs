@@ -0,0 +1,304 @@
+//! A non-standard variant of QOI whose index has `N` slots rather than 64, for experimenting with
+//! larger indexes, which let more pixels be encoded as index chunks instead of literals at the cost
+//! of a wider QOI_OP_INDEX chunk. Files using the variant are NOT valid QOI files and will be
+//! rejected by other QOI decoders.
+//!
+//! A file has the following 15-byte header, followed by the chunk stream and the standard end
+//! marker:
+//!
+//! ```text
+//! magic       [u8; 4]  "qoiw"
+//! width       u32      big-endian
+//! height      u32      big-endian
+//! channels    u8       same as QOI
+//! colorspace  u8       same as QOI
+//! index bits  u8       log2 of the number of index slots
+//! ```
+//!
+//! `N` must be a power of two no greater than `MAX_INDEX_SIZE`, and pixels are stored in slot
+//! `(r * 3 + g * 5 + b * 7 + a * 11) % N`. If `N` is at most 64, the chunks are laid out exactly as
+//! in standard QOI. Otherwise QOI_OP_INDEX takes two bytes, `0b00hhhhhh llllllll`, holding the high
+//! six bits and the low eight bits of the slot. The other chunks are unchanged. Since the hash is
+//! never more than 6630, an index with more than 8192 slots never uses its upper slots.
+//!
+//! Files are written with `Encoder::encode_header_wide` and read with
+//! `Decoder::decode_header_wide`, which must be given the same `N` the file was written with.
+
+use alloc::vec::Vec;
+
+use crate::byte_sink::ByteSink;
+use crate::byte_stream::{ByteStream, StreamError};
+use crate::decode::{decode_into_new_vec, DecodeAllError, MAX_RUN};
+use crate::encode::EncodeError;
+use crate::header::{Channels, Header};
+use crate::pixel::Pixel;
+use crate::pixel_index::PixelIndex;
+
+pub const MAGIC: [u8; 4] = *b"qoiw";
+
+/// The largest index size the variant supports, which is limited by the 14 bits of the two-byte
+/// QOI_OP_INDEX chunk.
+pub const MAX_INDEX_SIZE: usize = 1 << 14;
+
+/// Holds the value of the header's index bits field for an index with `N` slots. Evaluating it
+/// fails to compile if `N` is not a power of two or is larger than `MAX_INDEX_SIZE`.
+pub(crate) struct IndexBits<const N: usize>;
+
+impl<const N: usize> IndexBits<N> {
+    pub(crate) const VALUE: u8 = {
+        assert!(N.is_power_of_two(), "index size must be a power of two");
+        assert!(N <= MAX_INDEX_SIZE, "index size must be at most `MAX_INDEX_SIZE`");
+        N.trailing_zeros() as u8
+    };
+}
+
+/// Encodes pixels using an `N`-slot index. Created by `Encoder::encode_header_wide`.
+pub struct WidePixelEncoder<S, const N: usize> {
+    sink: S,
+    previous: Pixel,
+    index: PixelIndex<N>,
+    remaining: u64,
+    /// The number of repeats of `previous` which have not been written yet.
+    run: u8,
+    channels: Channels,
+}
+
+impl<S, const N: usize> WidePixelEncoder<S, N>
+where
+    S: ByteSink,
+{
+    pub(crate) fn new(sink: S, num_pixels: u64, channels: Channels) -> Self {
+        Self {
+            sink,
+            previous: Pixel::BLACK,
+            index: PixelIndex::new(),
+            remaining: num_pixels,
+            run: 0,
+            channels,
+        }
+    }
+
+    /// Encodes the given pixels, which continue on from any pixels encoded previously. Returns
+    /// `EncodeError::TooManyPixels` without encoding any of them if there are more pixels than the
+    /// image has left.
+    pub fn encode_pixels(&mut self, pixels: &[Pixel]) -> Result<(), EncodeError<S::IoError>> {
+        if pixels.len() as u64 > self.remaining {
+            return Err(EncodeError::TooManyPixels);
+        }
+
+        for &pixel in pixels {
+            self.encode_pixel_unchecked(pixel).map_err(EncodeError::Io)?;
+            self.remaining -= 1;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of pixels which must still be encoded to complete the image.
+    pub fn remaining_pixels(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Completes the image, writing any pending run followed by the end marker, and returns the
+    /// underlying sink. Returns `EncodeError::TooFewPixels` if fewer pixels were encoded than the
+    /// header says the image contains.
+    pub fn finish(mut self) -> Result<S, EncodeError<S::IoError>> {
+        if self.remaining > 0 {
+            return Err(EncodeError::TooFewPixels {
+                missing: self.remaining,
+            });
+        }
+
+        self.flush_run().map_err(EncodeError::Io)?;
+        self.sink.write_n(Header::END_MARKER).map_err(EncodeError::Io)?;
+
+        Ok(self.sink)
+    }
+
+    fn flush_run(&mut self) -> Result<(), S::IoError> {
+        if self.run > 0 {
+            self.sink.write_one(0xC0 | (self.run - 1))?;
+            self.run = 0;
+        }
+
+        Ok(())
+    }
+
+    fn encode_pixel_unchecked(&mut self, mut pixel: Pixel) -> Result<(), S::IoError> {
+        if self.channels == Channels::Rgb {
+            pixel.a = u8::MAX;
+        }
+
+        let previous = self.previous;
+
+        if pixel == previous {
+            self.run += 1;
+            if self.run == MAX_RUN {
+                self.flush_run()?;
+            }
+            return Ok(());
+        }
+
+        self.flush_run()?;
+        self.previous = pixel;
+
+        if self.index.contains(pixel) {
+            let slot = PixelIndex::<N>::pixel_hash(pixel);
+            return if N > 64 {
+                self.sink.write_n([(slot >> 8) as u8, slot as u8])
+            } else {
+                self.sink.write_one(slot as u8)
+            };
+        }
+
+        self.index.insert(pixel);
+
+        if let Some(chunk) = pixel.diff_chunk(previous) {
+            self.sink.write_one(chunk)
+        } else if let Some(chunk) = pixel.luma_chunk(previous) {
+            self.sink.write_n(chunk)
+        } else if pixel.a == previous.a {
+            let [r, g, b] = pixel.rgb();
+            self.sink.write_n([0xFE, r, g, b])
+        } else {
+            let [r, g, b, a] = pixel.rgba();
+            self.sink.write_n([0xFF, r, g, b, a])
+        }
+    }
+}
+
+/// Decodes pixels using an `N`-slot index. Created by `Decoder::decode_header_wide`.
+pub struct WidePixelDecoder<S, const N: usize> {
+    stream: S,
+    previous: Pixel,
+    index: PixelIndex<N>,
+    remaining: u64,
+    run: u8,
+}
+
+impl<S, const N: usize> WidePixelDecoder<S, N>
+where
+    S: ByteStream,
+{
+    pub(crate) fn new(stream: S, num_pixels: u64) -> Self {
+        Self {
+            stream,
+            previous: Pixel::BLACK,
+            index: PixelIndex::new(),
+            remaining: num_pixels,
+            run: 0,
+        }
+    }
+
+    /// Returns the number of pixels which are still to be decoded.
+    pub fn remaining_pixels(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Returns the underlying stream. Once every pixel has been decoded, it is positioned at the
+    /// end marker.
+    pub fn into_stream(self) -> S {
+        self.stream
+    }
+
+    /// Decodes pixels into the given buffer until the buffer becomes full or the end of the image
+    /// is reached, and returns the number of pixels written to the buffer.
+    pub fn decode_pixels_into(
+        &mut self,
+        buf: &mut [Pixel],
+    ) -> Result<usize, StreamError<S::IoError>> {
+        let num_pixels = match usize::try_from(self.remaining) {
+            Ok(remaining) if remaining < buf.len() => remaining,
+            _ => buf.len(),
+        };
+
+        for px in &mut buf[..num_pixels] {
+            if self.run > 0 {
+                self.run -= 1;
+            } else {
+                let b0 = self.stream.read_one()?;
+                self.decode_chunk(b0)?;
+            }
+
+            *px = self.previous;
+            self.remaining -= 1;
+        }
+
+        Ok(num_pixels)
+    }
+
+    /// Allocates a new vec large enough for all of the remaining pixels, decodes all of the
+    /// remaining pixels into the vec, and returns the buffer.
+    pub fn decode_pixels_vec(mut self) -> Result<Vec<Pixel>, DecodeAllError<S::IoError>> {
+        let expected = self.remaining;
+        let num_pixels = expected.try_into().map_err(|_| DecodeAllError::TooLarge)?;
+
+        decode_into_new_vec(num_pixels, Pixel::ZERO, |dst| {
+            self.decode_pixels_into(dst).map_err(|err| match err {
+                StreamError::UnexpectedEof => DecodeAllError::Truncated {
+                    decoded: expected - self.remaining,
+                    expected,
+                },
+                StreamError::Io(err) => DecodeAllError::Io(err),
+            })
+        })
+    }
+
+    /// Decodes a single chunk, as the standard decoder does except for the width of QOI_OP_INDEX.
+    fn decode_chunk(&mut self, b0: u8) -> Result<(), StreamError<S::IoError>> {
+        match b0 {
+            // QOI_OP_RGB
+            0xFE => {
+                let [r, g, b] = self.stream.read_n()?;
+                self.previous = Pixel::new(r, g, b, self.previous.a);
+                self.index.insert(self.previous);
+            }
+
+            // QOI_OP_RGBA
+            0xFF => {
+                let [r, g, b, a] = self.stream.read_n()?;
+                self.previous = Pixel::new(r, g, b, a);
+                self.index.insert(self.previous);
+            }
+
+            _ => match b0 >> 6 {
+                // QOI_OP_INDEX
+                0x0 => {
+                    let slot = if N > 64 {
+                        (usize::from(b0) << 8) | usize::from(self.stream.read_one()?)
+                    } else {
+                        usize::from(b0)
+                    };
+                    self.previous = self.index.masked_get(slot);
+                }
+
+                // QOI_OP_DIFF
+                0x1 => {
+                    self.previous.r = self.previous.r.wrapping_sub(2).wrapping_add((b0 >> 4) & 0x3);
+                    self.previous.g = self.previous.g.wrapping_sub(2).wrapping_add((b0 >> 2) & 0x3);
+                    self.previous.b = self.previous.b.wrapping_sub(2).wrapping_add(b0 & 0x3);
+                    self.index.insert(self.previous);
+                }
+
+                // QOI_OP_LUMA
+                0x2 => {
+                    let b1 = self.stream.read_one()?;
+                    let dg = (b0 & 0x3F).wrapping_sub(32);
+                    let dr = dg.wrapping_sub(8).wrapping_add(b1 >> 4);
+                    let db = dg.wrapping_sub(8).wrapping_add(b1 & 0x0F);
+                    self.previous.r = self.previous.r.wrapping_add(dr);
+                    self.previous.g = self.previous.g.wrapping_add(dg);
+                    self.previous.b = self.previous.b.wrapping_add(db);
+                    self.index.insert(self.previous);
+                }
+
+                // QOI_OP_RUN, where the first pixel of the run is the one being decoded now
+                _ => {
+                    self.run = b0 & 0x3F;
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
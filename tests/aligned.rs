@@ -0,0 +1,28 @@
+#![cfg(feature = "aligned")]
+
+use okay::aligned::{self, AlignedBuf};
+use okay::{Decoder, Pixel};
+
+#[test]
+fn aligned_buf_is_aligned() {
+    for align in [1, 2, 16, 64, 256, 4096] {
+        for len in [0, 1, 100, 5000] {
+            let buf = AlignedBuf::zeroed(len, align).unwrap();
+            assert_eq!(buf.len(), len);
+            assert!(aligned::is_aligned(&buf, align));
+            assert!(buf.iter().all(|&b| b == 0));
+        }
+    }
+}
+
+#[test]
+fn decode_bytes_aligned_matches_vec() {
+    let rgba = (0..35u8).flat_map(|i| [i, i * 3, 0, 255]).collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 5, 7).unwrap();
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let buf = decoder.decode_bytes_aligned(256, Pixel::rgba).unwrap();
+
+    assert!(aligned::is_aligned(&buf, 256));
+    assert_eq!(&buf[..], &rgba[..]);
+}
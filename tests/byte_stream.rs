@@ -0,0 +1,283 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::io::{self, BufReader, Read};
+use std::rc::Rc;
+
+use okay::byte_stream::{
+    BufReadByteStream, BufferedByteStream, ByteStream, PeekableByteStream, SliceByteStream,
+    StreamError, VecDequeByteStream,
+};
+use okay::decode::PartialDecode;
+use okay::{Decoder, Pixel};
+
+/// A reader which returns at most `max_len` bytes per read.
+struct Trickle<'a> {
+    bytes: &'a [u8],
+    max_len: usize,
+}
+
+impl<'a> Read for Trickle<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.max_len).min(self.bytes.len());
+        buf[..len].copy_from_slice(&self.bytes[..len]);
+        self.bytes = &self.bytes[len..];
+        Ok(len)
+    }
+}
+
+#[test]
+fn slice_peek_one() {
+    let mut stream = SliceByteStream::new(&[1, 2]);
+
+    assert_eq!(stream.peek_one().unwrap(), Some(1));
+    assert_eq!(stream.peek_one().unwrap(), Some(1));
+    assert_eq!(stream.bytes_consumed(), Some(0));
+
+    assert_eq!(stream.read_one().unwrap(), 1);
+    assert_eq!(stream.peek_one().unwrap(), Some(2));
+    assert_eq!(stream.read_one().unwrap(), 2);
+    assert_eq!(stream.peek_one().unwrap(), None);
+}
+
+#[test]
+fn buf_read_across_refills() {
+    let bytes = (0..=255).collect::<Vec<u8>>();
+    let reader = BufReader::with_capacity(3, Trickle { bytes: &bytes, max_len: 2 });
+    let mut stream = BufReadByteStream::new(reader);
+
+    assert_eq!(stream.read_n::<5>().unwrap(), [0, 1, 2, 3, 4]);
+    assert_eq!(stream.peek_one().unwrap(), Some(5));
+    assert_eq!(stream.read_one().unwrap(), 5);
+    assert_eq!(stream.read_n::<250>().unwrap()[..], bytes[6..]);
+    assert_eq!(stream.bytes_consumed(), Some(256));
+    assert_eq!(stream.peek_one().unwrap(), None);
+    assert!(matches!(stream.read_one(), Err(StreamError::UnexpectedEof)));
+}
+
+#[test]
+fn buf_read_decodes_like_slice() {
+    let mut bytes = b"qoif".to_vec();
+    bytes.extend_from_slice(&[0, 0, 0, 3, 0, 0, 0, 1, 4, 0]);
+    bytes.extend_from_slice(&[0xFF, 1, 2, 3, 4, 0xC0, 0x6B]);
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+
+    let (_, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let expected = decoder.decode_pixels_vec().unwrap();
+
+    let reader = BufReader::with_capacity(4, Trickle { bytes: &bytes, max_len: 3 });
+    let (_, decoder) = Decoder::new_from_buf_reader(reader)
+        .try_decode_header()
+        .ok()
+        .unwrap()
+        .unwrap();
+    assert_eq!(decoder.decode_pixels_vec().unwrap(), expected);
+}
+
+#[test]
+fn vec_deque_pops_from_front() {
+    let mut deque = VecDeque::new();
+    deque.extend([1, 2, 3]);
+    let mut stream = VecDequeByteStream::new(&mut deque);
+
+    assert_eq!(stream.read_n::<2>().unwrap(), [1, 2]);
+    // Too few bytes are buffered, so nothing is consumed
+    assert!(matches!(stream.read_n::<2>(), Err(StreamError::UnexpectedEof)));
+    assert_eq!(stream.peek_one().unwrap(), Some(3));
+
+    stream.inner_mut().extend([4, 5]);
+    assert_eq!(stream.read_n::<3>().unwrap(), [3, 4, 5]);
+    assert_eq!(stream.bytes_consumed(), Some(5));
+    assert!(stream.inner().is_empty());
+}
+
+#[test]
+fn vec_deque_decodes() {
+    let bytes = qoi::encode_to_vec([10, 20, 30, 255, 10, 20, 30, 255, 11, 21, 31, 255], 3, 1)
+        .unwrap();
+
+    let mut deque = VecDeque::from(bytes);
+    let stream = VecDequeByteStream::new(&mut deque);
+    let (_header, decoder) = Decoder::new(stream).decode_header().unwrap();
+    assert_eq!(
+        decoder.decode_pixels_vec().unwrap(),
+        [
+            Pixel::new(10, 20, 30, 255),
+            Pixel::new(10, 20, 30, 255),
+            Pixel::new(11, 21, 31, 255),
+        ]
+    );
+    // Only the end marker is left
+    assert_eq!(deque.len(), 8);
+}
+
+/// A queue shared between a test and a decoder, so that the test can add bytes while the decoder
+/// holds the stream.
+#[derive(Clone, Default)]
+struct SharedQueue(Rc<RefCell<VecDeque<u8>>>);
+
+impl ByteStream for SharedQueue {
+    type IoError = Infallible;
+
+    fn read_n<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Infallible>> {
+        VecDequeByteStream::new(&mut self.0.borrow_mut()).read_n()
+    }
+}
+
+impl PeekableByteStream for SharedQueue {
+    fn peek_one(&mut self) -> Result<Option<u8>, StreamError<Infallible>> {
+        Ok(self.0.borrow().front().copied())
+    }
+}
+
+impl BufferedByteStream for SharedQueue {
+    fn buffered_len(&self) -> usize {
+        self.0.borrow().len()
+    }
+}
+
+#[test]
+fn partial_decode() {
+    let rgba = (0..200u8)
+        .flat_map(|i| [i / 4, i / 8, 255 - i / 4, if i % 50 < 25 { 255 } else { i }])
+        .collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 20, 10).unwrap();
+    let (_header, expected) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let expected = expected.decode_pixels_vec().unwrap();
+
+    let queue = SharedQueue::default();
+    queue.0.borrow_mut().extend(&bytes[..14]);
+    let (_header, mut decoder) = Decoder::new(queue.clone()).decode_header().unwrap();
+
+    let mut buf = [Pixel::ZERO; 16];
+    assert_eq!(
+        decoder.decode_pixels_into_partial(&mut buf).unwrap(),
+        (0, PartialDecode::Pending)
+    );
+
+    // Feed the chunks in three-byte pieces, which often split chunks in two
+    let mut pixels = Vec::new();
+    let mut status = PartialDecode::Pending;
+
+    for piece in bytes[14..].chunks(3) {
+        queue.0.borrow_mut().extend(piece);
+        loop {
+            let (n, next_status) = decoder.decode_pixels_into_partial(&mut buf).unwrap();
+            pixels.extend_from_slice(&buf[..n]);
+            status = next_status;
+            if status != PartialDecode::BufferFull {
+                break;
+            }
+        }
+    }
+
+    assert_eq!(status, PartialDecode::Finished);
+    assert_eq!(pixels, expected);
+    // Only the end marker is left
+    assert_eq!(queue.buffered_len(), 8);
+}
+
+#[test]
+fn resume_after_eof() {
+    let rgba = (0..200u8)
+        .flat_map(|i| [i / 4, i / 8, 255 - i / 4, if i % 50 < 25 { 255 } else { i }])
+        .collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 20, 10).unwrap();
+    let (_header, expected) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let expected = expected.decode_pixels_vec().unwrap();
+
+    let queue = SharedQueue::default();
+    queue.0.borrow_mut().extend(&bytes[..14]);
+    let (_header, mut decoder) = Decoder::new(queue.clone()).decode_header().unwrap();
+
+    // Feed the chunks one byte at a time, so that every multi-byte chunk hits the end of the
+    // stream partway through
+    let mut pixels = Vec::new();
+    let mut num_eofs = 0;
+
+    for &byte in &bytes[14..] {
+        queue.0.borrow_mut().push_back(byte);
+        loop {
+            match decoder.decode_pixel() {
+                Ok(Some(pixel)) => pixels.push(pixel),
+                Ok(None) => break,
+                Err(StreamError::UnexpectedEof) => {
+                    num_eofs += 1;
+                    break;
+                }
+                Err(err) => panic!("unexpected error: {:?}", err),
+            }
+        }
+    }
+
+    assert!(num_eofs > 0);
+    assert_eq!(pixels, expected);
+    assert_eq!(decoder.remaining_pixels(), 0);
+}
+
+#[test]
+fn budgeted_resume_after_eof() {
+    let rgba = (0..200u8)
+        .flat_map(|i| [i / 4, i / 8, 255 - i / 4, if i % 50 < 25 { 255 } else { i }])
+        .collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 20, 10).unwrap();
+    let (_header, expected) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let expected = expected.decode_pixels_vec().unwrap();
+
+    let queue = SharedQueue::default();
+    queue.0.borrow_mut().extend(&bytes[..14]);
+    let (_header, mut decoder) = Decoder::new(queue.clone()).decode_header().unwrap();
+
+    // Feed the chunks in five-byte pieces with a budget larger than a piece can fill, so that most
+    // calls decode some pixels before hitting the end of the stream
+    let mut pixels = Vec::new();
+    let mut num_eofs = 0;
+    let mut finished = false;
+
+    for piece in bytes[14..].chunks(5) {
+        queue.0.borrow_mut().extend(piece);
+        while !finished {
+            match decoder.decode_pixels_budgeted(&mut pixels, 32) {
+                Ok((_, exhausted)) => finished = exhausted,
+                Err(StreamError::UnexpectedEof) => {
+                    num_eofs += 1;
+                    break;
+                }
+                Err(err) => panic!("unexpected error: {:?}", err),
+            }
+        }
+    }
+
+    assert!(finished);
+    assert!(num_eofs > 0);
+    assert_eq!(pixels, expected);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "bytes for a single chunk")]
+fn chunk_len_checked_in_debug() {
+    /// A stream which claims to consume twice as many bytes as it returns, as a stream with a buggy
+    /// `bytes_consumed` or a decoder which skipped over data would.
+    struct Overcounting<'a>(SliceByteStream<'a>);
+
+    impl<'a> ByteStream for Overcounting<'a> {
+        type IoError = Infallible;
+
+        fn read_n<const N: usize>(&mut self) -> Result<[u8; N], StreamError<Infallible>> {
+            self.0.read_n()
+        }
+
+        fn bytes_consumed(&self) -> Option<u64> {
+            self.0.bytes_consumed().map(|consumed| consumed * 2)
+        }
+    }
+
+    // The changing alpha makes every pixel a five-byte QOI_OP_RGBA chunk
+    let rgba = (0..16u8).flat_map(|i| [i * 16, 0, 0, 254 - i]).collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 4, 4).unwrap();
+
+    let stream = Overcounting(SliceByteStream::new(&bytes));
+    let (_header, decoder) = Decoder::new(stream).decode_header().unwrap();
+    let _ = decoder.decode_pixels_vec();
+}
@@ -0,0 +1,12 @@
+use okay::Header;
+
+/// Builds a QOI file with the given header fields and chunks, followed by the end marker.
+pub fn qoi_bytes(width: u32, height: u32, channels: u8, chunks: &[u8]) -> Vec<u8> {
+    let mut bytes = b"qoif".to_vec();
+    bytes.extend_from_slice(&width.to_be_bytes());
+    bytes.extend_from_slice(&height.to_be_bytes());
+    bytes.extend_from_slice(&[channels, 0]);
+    bytes.extend_from_slice(chunks);
+    bytes.extend_from_slice(&Header::END_MARKER);
+    bytes
+}
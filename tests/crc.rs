@@ -0,0 +1,61 @@
+#![cfg(feature = "crc")]
+
+use okay::byte_sink::VecByteSink;
+use okay::byte_stream::SliceByteStream;
+use okay::crc::{CrcByteSink, CrcByteStream};
+use okay::decode::DecodeAllError;
+use okay::header::{Channels, ColSpace};
+use okay::{Decoder, Encoder, Header, Pixel};
+
+fn pixels() -> Vec<Pixel> {
+    (0..100u8).map(|i| Pixel::new(i, i / 3, 255 - i, 255)).collect()
+}
+
+fn encode_with_crc() -> Vec<u8> {
+    let header = Header::new(10, 10, Channels::Rgba, ColSpace::Srgb);
+    let mut encoder = Encoder::new(CrcByteSink::new(VecByteSink::new(Vec::new())), header)
+        .encode_header()
+        .unwrap();
+    encoder.encode_pixels(&pixels()).unwrap();
+    encoder.finish_with_crc().unwrap().into_inner()
+}
+
+fn verify(bytes: &[u8]) -> Result<(), DecodeAllError<std::convert::Infallible>> {
+    let stream = CrcByteStream::new(SliceByteStream::new(bytes));
+    let (_header, decoder) = Decoder::new(stream).decode_header().unwrap();
+    decoder.verify_crc()
+}
+
+#[test]
+fn appends_crc() {
+    let bytes = encode_with_crc();
+    let plain = Encoder::encode_to_vec(
+        Header::new(10, 10, Channels::Rgba, ColSpace::Srgb),
+        &pixels(),
+    )
+    .unwrap();
+
+    assert_eq!(bytes[..plain.len()], plain);
+    assert_eq!(bytes[plain.len()..], crc32fast::hash(&plain).to_be_bytes());
+
+    // Decoders which don't know about the CRC stop at the end marker
+    let (_, decoded) = qoi::decode_to_vec(&bytes).unwrap();
+    assert_eq!(decoded.len(), 400);
+}
+
+#[test]
+fn verify_crc() {
+    let bytes = encode_with_crc();
+    verify(&bytes).unwrap();
+
+    // Change the colour space, which still decodes but is not what was written
+    let mut corrupt = bytes.clone();
+    corrupt[13] = 1;
+    assert!(matches!(
+        verify(&corrupt),
+        Err(DecodeAllError::CrcMismatch { .. })
+    ));
+
+    let missing = &bytes[..(bytes.len() - 4)];
+    assert!(matches!(verify(missing), Err(DecodeAllError::UnexpectedEof)));
+}
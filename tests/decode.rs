@@ -0,0 +1,794 @@
+use std::io::{self, Read};
+
+use okay::byte_stream::ByteStream;
+use okay::header::{Channels, ColSpace};
+use okay::decode::{DecodeAllError, HeaderDecodeError, RangeDecoder};
+use okay::{Decoder, DecoderOptions, Encoder, Header, Pixel};
+
+mod common;
+
+use common::qoi_bytes;
+
+fn decode(bytes: &[u8]) -> Vec<Pixel> {
+    let (_header, decoder) = Decoder::new_from_slice(bytes).decode_header().unwrap();
+    decoder.decode_pixels_vec().unwrap()
+}
+
+#[test]
+fn one_pixel_rgb() {
+    let bytes = qoi_bytes(1, 1, 3, &[0xFE, 10, 20, 30]);
+    assert_eq!(decode(&bytes), [Pixel::new(10, 20, 30, 255)]);
+}
+
+#[test]
+fn one_pixel_rgba() {
+    let bytes = qoi_bytes(1, 1, 4, &[0xFF, 10, 20, 30, 40]);
+    assert_eq!(decode(&bytes), [Pixel::new(10, 20, 30, 40)]);
+}
+
+#[test]
+fn one_pixel_black_run() {
+    // A single pixel equal to the initial previous pixel is encoded as a run of length 1
+    let bytes = qoi_bytes(1, 1, 4, &[0xC0]);
+    assert_eq!(decode(&bytes), [Pixel::BLACK]);
+}
+
+#[test]
+fn one_pixel_reference_encoder() {
+    for pixel in [Pixel::BLACK, Pixel::ZERO, Pixel::new(1, 2, 3, 4)] {
+        let bytes = qoi::encode_to_vec(pixel.rgba(), 1, 1).unwrap();
+        assert_eq!(decode(&bytes), [pixel]);
+    }
+}
+
+#[test]
+fn arc_matches_vec() {
+    let rgba = (0..48u8).flat_map(|i| [i, i / 2, 7, 255]).collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 8, 6).unwrap();
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let arc = decoder.decode_pixels_arc().unwrap();
+
+    assert_eq!(&arc[..], &decode(&bytes)[..]);
+}
+
+#[test]
+fn by_row_matches_vec() {
+    // Long runs of the same colour which cross row boundaries
+    let rgba = (0..35u8).flat_map(|i| [i / 8, 0, 0, 255]).collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 5, 7).unwrap();
+    let expected = decode(&bytes);
+
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let mut rows = Vec::new();
+    decoder
+        .decode_by_row(&header, |y, row| rows.push((y, row.to_vec())))
+        .unwrap();
+
+    assert_eq!(rows.len(), 7);
+    for (i, (y, row)) in rows.into_iter().enumerate() {
+        assert_eq!(y as usize, i);
+        assert_eq!(row, &expected[(i * 5)..((i + 1) * 5)]);
+    }
+}
+
+#[test]
+fn budgeted_matches_vec() {
+    let rgba = (0..35u8).flat_map(|i| [i, i * 3, 0, 255]).collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 5, 7).unwrap();
+
+    let (_header, mut decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let mut buf = Vec::new();
+    let mut calls = 0;
+    loop {
+        calls += 1;
+        let (n, exhausted) = decoder.decode_pixels_budgeted(&mut buf, 8).unwrap();
+        assert!(n <= 8);
+        if exhausted {
+            break;
+        }
+    }
+
+    assert_eq!(calls, 5);
+    assert_eq!(buf, decode(&bytes));
+}
+
+/// A reader which returns at most one byte per call to `read`.
+struct OneByteReader<'a>(&'a [u8]);
+
+impl<'a> Read for OneByteReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.0.len()).min(1);
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn stream_implementations_agree() {
+    // Pseudo-random image with a mix of all chunk types
+    let mut state = 0x1234_5678u32;
+    let rgba = (0..(37 * 23))
+        .flat_map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let [a, b, c, d] = state.to_be_bytes();
+            match a % 4 {
+                0 => [b, c, d, 255],
+                1 => [b, c, d, a],
+                _ => [0, 0, b % 3, 255],
+            }
+        })
+        .collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 37, 23).unwrap();
+
+    let from_slice = decode(&bytes);
+
+    let (_header, decoder) = Decoder::new_from_iter(bytes.iter().copied()).decode_header().unwrap();
+    let from_iter = decoder.decode_pixels_vec().unwrap();
+
+    let (_header, decoder) = Decoder::new_from_reader(&bytes[..]).decode_header().unwrap();
+    let from_reader = decoder.decode_pixels_vec().unwrap();
+
+    let (_header, decoder) = Decoder::new_from_reader(OneByteReader(&bytes)).decode_header().unwrap();
+    let from_slow_reader = decoder.decode_pixels_vec().unwrap();
+
+    assert_eq!(from_slice.iter().flat_map(|p| p.rgba()).collect::<Vec<_>>(), rgba);
+    assert_eq!(from_iter, from_slice);
+    assert_eq!(from_reader, from_slice);
+    assert_eq!(from_slow_reader, from_slice);
+}
+
+#[test]
+fn converting_col_space() {
+    let rgba = [128, 0, 255, 100];
+    let mut bytes = qoi::encode_to_vec(rgba, 1, 1).unwrap();
+
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let pixels = decoder.decode_converting(&header, ColSpace::Linear).unwrap();
+    assert_eq!(pixels, [Pixel::new(55, 0, 255, 100)]);
+
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let pixels = decoder.decode_converting(&header, ColSpace::Srgb).unwrap();
+    assert_eq!(pixels, [Pixel::new(128, 0, 255, 100)]);
+
+    // Mark the image as linear
+    bytes[13] = 1;
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let pixels = decoder.decode_converting(&header, ColSpace::Srgb).unwrap();
+    assert_eq!(pixels, [Pixel::new(188, 0, 255, 100)]);
+}
+
+#[test]
+fn row_byte_counts() {
+    let chunks = [
+        // Row 0: RGBA, then a run of 3 continuing onto row 1
+        0xFF, 1, 2, 3, 4, 0xC2,
+        // Row 1: the run covers the first pixel, then RGB and LUMA
+        0xFE, 5, 6, 7, 0xA0, 0x88,
+        // Row 2: DIFF, INDEX, run of 1
+        0x6A, 0x00, 0xC0,
+    ];
+    let bytes = qoi_bytes(3, 3, 4, &chunks);
+
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let counts = decoder.decode_row_byte_counts(&header).unwrap();
+
+    assert_eq!(counts, [6, 6, 3]);
+}
+
+#[test]
+fn max_megapixels() {
+    // 2000x1500 is exactly 3 megapixels. The pixel data is never read, so it can be empty
+    let bytes = qoi_bytes(2000, 1500, 4, &[]);
+
+    let options = DecoderOptions::new().max_megapixels(3.0);
+    assert!(Decoder::new_from_slice(&bytes).with_options(options).decode_header().is_ok());
+
+    let options = DecoderOptions::new().max_megapixels(2.5);
+    match Decoder::new_from_slice(&bytes).with_options(options).decode_header() {
+        Err(HeaderDecodeError::TooLarge { megapixels }) => assert_eq!(megapixels, 3.0),
+        res => panic!("expected TooLarge, got {:?}", res.map(|(header, _)| header)),
+    }
+}
+
+#[test]
+fn peek_header() {
+    let bytes = qoi_bytes(2, 1, 4, &[0xFE, 10, 20, 30, 0xC0]);
+    let decoder = Decoder::new_from_slice(&bytes);
+
+    let header = decoder.peek_header().unwrap();
+    assert_eq!(header, Header::new(2, 1, Channels::Rgba, ColSpace::Srgb));
+
+    let (decoded_header, decoder) = decoder.decode_header().unwrap();
+    assert_eq!(decoded_header, header);
+    assert_eq!(decoder.decode_pixels_vec().unwrap(), [Pixel::new(10, 20, 30, 255); 2]);
+
+    let decoder = Decoder::new_from_slice(&bytes).with_pixel_limit(1);
+    assert!(matches!(decoder.peek_header(), Err(HeaderDecodeError::TooLarge { .. })));
+}
+
+#[test]
+fn pixel_limit() {
+    let bytes = qoi_bytes(2000, 1500, 4, &[]);
+
+    let decoder = Decoder::new_from_slice(&bytes).with_pixel_limit(3_000_000);
+    assert!(decoder.decode_header().is_ok());
+
+    let decoder = Decoder::new_from_slice(&bytes).with_pixel_limit(2_999_999);
+    match decoder.decode_header() {
+        Err(HeaderDecodeError::TooLarge { megapixels }) => assert_eq!(megapixels, 3.0),
+        res => panic!("expected TooLarge, got {:?}", res.map(|(header, _)| header)),
+    }
+
+    // The limit applies on top of any other options
+    let options = DecoderOptions::new().max_megapixels(2.5);
+    let decoder = Decoder::new_from_slice(&bytes)
+        .with_options(options)
+        .with_pixel_limit(u64::MAX);
+    assert!(decoder.decode_header().is_err());
+}
+
+#[test]
+fn rgb_dropping_alpha() {
+    let bytes = qoi::encode_to_vec([1, 2, 3, 255, 4, 5, 6, 255], 2, 1).unwrap();
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(decoder.decode_rgb_vec_dropping_alpha().unwrap(), (vec![1, 2, 3, 4, 5, 6], true));
+
+    let bytes = qoi::encode_to_vec([1, 2, 3, 255, 4, 5, 6, 254], 2, 1).unwrap();
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(decoder.decode_rgb_vec_dropping_alpha().unwrap(), (vec![1, 2, 3, 4, 5, 6], false));
+}
+
+#[test]
+fn alpha_bounds() {
+    // 4x3 transparent image with visible pixels at (1, 0) and (2, 1)
+    let mut rgba = vec![0; 4 * 3 * 4];
+    rgba[4..8].copy_from_slice(&[10, 20, 30, 1]);
+    rgba[(4 + 2) * 4..(4 + 3) * 4].copy_from_slice(&[10, 20, 30, 255]);
+    let bytes = qoi::encode_to_vec(&rgba, 4, 3).unwrap();
+
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let (pixels, bounds) = decoder.decode_with_alpha_bounds(&header).unwrap();
+    assert_eq!(pixels, decode(&bytes));
+    assert_eq!(bounds, Some((1, 0, 2, 2)));
+
+    let bytes = qoi::encode_to_vec([0; 4 * 3 * 4], 4, 3).unwrap();
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(decoder.decode_with_alpha_bounds(&header).unwrap().1, None);
+
+    let bytes = qoi::encode_to_vec([255; 4 * 3 * 3], 4, 3).unwrap();
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(decoder.decode_with_alpha_bounds(&header).unwrap().1, Some((0, 0, 4, 3)));
+}
+
+#[test]
+fn average_color() {
+    let rgba = [
+        10, 20, 30, 255,
+        20, 40, 61, 255,
+        200, 200, 200, 0,
+    ];
+    let bytes = qoi::encode_to_vec(rgba, 3, 1).unwrap();
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(decoder.decode_average_color().unwrap(), Pixel::new(77, 87, 97, 170));
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(
+        decoder.decode_average_color_alpha_weighted().unwrap(),
+        Pixel::new(15, 30, 46, 170)
+    );
+}
+
+#[test]
+fn average_color_large() {
+    // More pixels than fit in a single batch, with rows of alternating black and white
+    let rgba = (0..1000u32)
+        .flat_map(|i| if (i / 10) % 2 == 0 { [0, 0, 0, 255] } else { [255, 255, 255, 255] })
+        .collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 10, 100).unwrap();
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(decoder.decode_average_color().unwrap(), Pixel::new(128, 128, 128, 255));
+}
+
+#[test]
+fn range_decoder_matches_vec() {
+    let rgba = (0..(9 * 7u32))
+        .flat_map(|i| [(i / 5) as u8, (i % 3) as u8, 200, if i % 11 == 0 { 7 } else { 255 }])
+        .collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 9, 7).unwrap();
+    let expected = decode(&bytes);
+
+    // Push the file in ranges of every size, including ranges which split chunks and the header
+    for range_len in 1..=bytes.len() {
+        let mut decoder = RangeDecoder::new();
+        let mut rows = Vec::new();
+
+        for range in bytes.chunks(range_len) {
+            decoder.push(range);
+            while let Some((y, row)) = decoder.next_row().unwrap() {
+                rows.push((y, row.to_vec()));
+            }
+        }
+
+        assert!(decoder.is_finished());
+        assert_eq!(rows.len(), 7);
+        for (i, (y, row)) in rows.into_iter().enumerate() {
+            assert_eq!(y as usize, i);
+            assert_eq!(row, &expected[(i * 9)..((i + 1) * 9)]);
+        }
+    }
+}
+
+#[test]
+fn range_decoder_from_buf_read() {
+    let rgba = (0..64u8).flat_map(|i| [i, i, i, 255]).collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 8, 8).unwrap();
+
+    let mut reader = io::BufReader::with_capacity(5, &bytes[..]);
+    let mut decoder = RangeDecoder::new();
+    let mut pixels = Vec::new();
+
+    while decoder.push_from(&mut reader).unwrap() > 0 {
+        while let Some((_y, row)) = decoder.next_row().unwrap() {
+            pixels.extend_from_slice(row);
+        }
+    }
+
+    assert!(decoder.is_finished());
+    assert_eq!(pixels, decode(&bytes));
+}
+
+#[test]
+fn alpha_bitmask() {
+    // 10x2 image whose alpha is 0, 100 or 200 in a repeating pattern
+    let rgba = (0..20u8).flat_map(|i| [i, 0, 0, (i % 3) * 100]).collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 10, 2).unwrap();
+
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let mask = decoder.decode_alpha_bitmask(&header, 150).unwrap();
+
+    // Pixels 2, 5, 8, 11, 14, 17 have alpha 200. Each row is padded to 2 bytes
+    assert_eq!(mask, [0b0010_0100, 0b1000_0000, 0b0100_1001, 0b0000_0000]);
+}
+
+#[test]
+fn index_fallback() {
+    let magenta = Pixel::new(255, 0, 255, 255);
+    // RGB, then an INDEX to a slot which was never written, then an INDEX to slot 0
+    let bytes = qoi_bytes(3, 1, 4, &[0xFE, 100, 150, 200, 0x05, 0x00]);
+
+    assert_eq!(
+        decode(&bytes),
+        [Pixel::new(100, 150, 200, 255), Pixel::ZERO, Pixel::ZERO]
+    );
+
+    let options = DecoderOptions::new().index_fallback(Some(magenta));
+    let (_header, decoder) = Decoder::new_from_slice(&bytes)
+        .with_options(options)
+        .decode_header()
+        .unwrap();
+    assert_eq!(
+        decoder.decode_pixels_vec().unwrap(),
+        [Pixel::new(100, 150, 200, 255), magenta, Pixel::ZERO]
+    );
+}
+
+#[test]
+fn maximum_dimensions() {
+    let bytes = qoi_bytes(u32::MAX, u32::MAX, 4, &[]);
+
+    // The pixel count fits in a u64, so the header itself is accepted
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(header.dimensions(), (u32::MAX, u32::MAX));
+    assert_eq!(decoder.remaining_pixels(), u32::MAX as u64 * u32::MAX as u64);
+    assert!(matches!(decoder.decode_pixels_vec(), Err(DecodeAllError::TooLarge)));
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert!(matches!(decoder.decode_bytes_vec(Pixel::rgba), Err(DecodeAllError::TooLarge)));
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert!(matches!(decoder.decode_pixels_arc(), Err(DecodeAllError::TooLarge)));
+
+    let options = DecoderOptions::new().max_megapixels(1000.0);
+    assert!(matches!(
+        Decoder::new_from_slice(&bytes).with_options(options).decode_header(),
+        Err(HeaderDecodeError::TooLarge { .. })
+    ));
+}
+
+#[test]
+fn checked_end_marker() {
+    let bytes = qoi_bytes(2, 1, 4, &[0xFE, 10, 20, 30, 0xC0]);
+    let decode_checked = |bytes: &[u8]| {
+        let (_header, decoder) = Decoder::new_from_slice(bytes).decode_header().unwrap();
+        decoder.decode_pixels_vec_checked()
+    };
+
+    assert_eq!(decode_checked(&bytes).unwrap(), [Pixel::new(10, 20, 30, 255); 2]);
+
+    let mut bad = bytes.clone();
+    *bad.last_mut().unwrap() = 0x02;
+    match decode_checked(&bad) {
+        Err(DecodeAllError::BadEndMarker { found }) => {
+            assert_eq!(found, [0, 0, 0, 0, 0, 0, 0, 2]);
+        }
+        res => panic!("expected a bad end marker, got {:?}", res),
+    }
+
+    let truncated = &bytes[..(bytes.len() - 1)];
+    assert!(matches!(decode_checked(truncated), Err(DecodeAllError::UnexpectedEof)));
+
+    // Single-pixel images, where the end marker directly follows the only chunk
+    let one_pixel = [
+        (qoi_bytes(1, 1, 3, &[0xFE, 10, 20, 30]), Pixel::new(10, 20, 30, 255)),
+        (qoi_bytes(1, 1, 4, &[0xFF, 10, 20, 30, 40]), Pixel::new(10, 20, 30, 40)),
+        (qoi_bytes(1, 1, 4, &[0xC0]), Pixel::BLACK),
+    ];
+
+    for (bytes, pixel) in &one_pixel {
+        assert_eq!(decode_checked(bytes).unwrap(), [*pixel]);
+
+        let end_marker_start = bytes.len() - Header::END_MARKER.len();
+        for i in [0, 6, 7] {
+            let mut bad = bytes.clone();
+            bad[end_marker_start + i] ^= 0x10;
+            assert!(matches!(decode_checked(&bad), Err(DecodeAllError::BadEndMarker { .. })));
+        }
+
+        for len in [end_marker_start, end_marker_start + 1, bytes.len() - 1] {
+            assert!(matches!(
+                decode_checked(&bytes[..len]),
+                Err(DecodeAllError::UnexpectedEof)
+            ));
+        }
+    }
+}
+
+#[test]
+fn iterate_pixels() {
+    let bytes = qoi_bytes(4, 1, 4, &[0xFE, 10, 20, 30, 0xC1, 0x6A]);
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+
+    let pixels = decoder.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(pixels, decode(&bytes));
+    assert_eq!(pixels.len(), 4);
+
+    let truncated = &bytes[..17];
+    let (_header, mut decoder) = Decoder::new_from_slice(truncated).decode_header().unwrap();
+    assert!(decoder.next().unwrap().is_err());
+    assert!(decoder.next().is_none());
+}
+
+#[test]
+fn decode_pixel() {
+    let bytes = qoi_bytes(3, 1, 4, &[0xFE, 10, 20, 30, 0xC0, 0x6B]);
+    let (_header, mut decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+
+    assert_eq!(decoder.decode_pixel().unwrap(), Some(Pixel::new(10, 20, 30, 255)));
+    assert_eq!(decoder.decode_pixel().unwrap(), Some(Pixel::new(10, 20, 30, 255)));
+    assert_eq!(decoder.remaining_pixels(), 1);
+    assert_eq!(decoder.decode_pixel().unwrap(), Some(Pixel::new(10, 20, 31, 255)));
+    assert_eq!(decoder.decode_pixel().unwrap(), None);
+}
+
+#[test]
+fn native_bytes() {
+    let chunks = [0xFE, 10, 20, 30, 0xFF, 1, 2, 3, 4];
+
+    let bytes = qoi_bytes(2, 1, 3, &chunks);
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let (buf, stride) = decoder.decode_native_bytes_vec(&header).unwrap();
+    assert_eq!(stride, 3);
+    assert_eq!(buf, [10, 20, 30, 1, 2, 3]);
+
+    let bytes = qoi_bytes(2, 1, 4, &chunks);
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let (buf, stride) = decoder.decode_native_bytes_vec(&header).unwrap();
+    assert_eq!(stride, 4);
+    assert_eq!(buf, [10, 20, 30, 255, 1, 2, 3, 4]);
+}
+
+#[test]
+fn decode_rows() {
+    // A 2x3 image whose rows are red, green and blue
+    let bytes = qoi_bytes(
+        2,
+        3,
+        4,
+        &[0xFE, 255, 0, 0, 0xC0, 0xFE, 0, 255, 0, 0xC0, 0xFE, 0, 0, 255, 0xC0],
+    );
+    let (header, mut decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let mut buf = [Pixel::ZERO; 4];
+
+    assert_eq!(decoder.decode_rows(header.width(), 1, &mut buf).unwrap(), 1);
+    assert_eq!(buf[..2], [Pixel::new(255, 0, 0, 255); 2]);
+
+    assert_eq!(decoder.decode_rows(header.width(), 2, &mut buf).unwrap(), 2);
+    assert_eq!(buf[..2], [Pixel::new(0, 255, 0, 255); 2]);
+    assert_eq!(buf[2..], [Pixel::new(0, 0, 255, 255); 2]);
+
+    assert_eq!(decoder.decode_rows(header.width(), 2, &mut buf).unwrap(), 0);
+}
+
+#[test]
+fn bytes_consumed() {
+    let chunks = [0xFE, 10, 20, 30, 0xC0, 0x6B];
+    let mut bytes = qoi_bytes(3, 1, 4, &chunks);
+    bytes.extend_from_slice(b"trailing data");
+
+    let (_header, mut decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(decoder.bytes_consumed(), Some(14));
+    decoder.decode_pixels_into(&mut [Pixel::ZERO; 3]).unwrap();
+    assert_eq!(decoder.bytes_consumed(), Some(14 + chunks.len() as u64));
+
+    let (_header, mut decoder) = Decoder::new_from_reader(&bytes[..]).decode_header().unwrap();
+    decoder.decode_pixels_into(&mut [Pixel::ZERO; 3]).unwrap();
+    assert_eq!(decoder.bytes_consumed(), Some(14 + chunks.len() as u64));
+
+    let (_header, mut decoder) = Decoder::new_from_iter(bytes.iter().copied())
+        .decode_header()
+        .unwrap();
+    decoder.decode_pixels_into(&mut [Pixel::ZERO; 3]).unwrap();
+    assert_eq!(decoder.bytes_consumed(), Some(14 + chunks.len() as u64));
+}
+
+#[test]
+fn skip_pixels() {
+    // Runs of varying lengths, with repeated colours so that some are decoded from the index
+    let pixels = (0..200u32)
+        .flat_map(|i| {
+            let pixel = Pixel::new((i % 7) as u8 * 30, (i % 3) as u8, 100, 255);
+            std::iter::repeat(pixel).take((i % 5) as usize * 20 + 1)
+        })
+        .collect::<Vec<_>>();
+    let header = Header::new(pixels.len() as u32, 1, Channels::Rgba, ColSpace::Srgb);
+    let bytes = Encoder::encode_to_vec(header, &pixels).unwrap();
+
+    for &n in &[0, 1, 20, 21, 100, 1000, pixels.len() - 1] {
+        let (_, mut decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+        assert_eq!(decoder.skip_pixels(n as u64).unwrap(), n as u64);
+        assert_eq!(decoder.remaining_pixels(), (pixels.len() - n) as u64);
+        assert_eq!(decoder.decode_pixels_vec().unwrap(), pixels[n..]);
+    }
+
+    let (_, mut decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(decoder.skip_pixels(u64::MAX).unwrap(), pixels.len() as u64);
+    assert_eq!(decoder.decode_pixel().unwrap(), None);
+}
+
+#[test]
+fn restore_state() {
+    let pixels = (0..300u32)
+        .map(|i| Pixel::new((i % 11) as u8 * 20, (i / 40) as u8, 7, 255))
+        .collect::<Vec<_>>();
+    let header = Header::new(30, 10, Channels::Rgba, ColSpace::Srgb);
+    let bytes = Encoder::encode_to_vec(header, &pixels).unwrap();
+
+    let (_, mut decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    decoder.skip_pixels(123).unwrap();
+    let state = decoder.state();
+    let offset = decoder.bytes_consumed().unwrap() as usize;
+    assert_eq!(state.remaining_pixels(), 300 - 123);
+
+    // Resume from the same offset with a decoder which has only seen the header
+    let resumed = bytes[..14].iter().chain(&bytes[offset..]).copied();
+    let (_, mut decoder) = Decoder::new_from_iter(resumed).decode_header().unwrap();
+    decoder.restore_state(state);
+    assert_eq!(decoder.decode_pixels_vec().unwrap(), pixels[123..]);
+}
+
+#[test]
+fn truncated() {
+    // A 4x4 image whose chunks stop after 10 pixels
+    let bytes = qoi_bytes(4, 4, 4, &[0xFE, 10, 20, 30, 0xC8]);
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes[..(bytes.len() - 8)])
+        .decode_header()
+        .unwrap();
+    assert!(matches!(
+        decoder.decode_pixels_vec(),
+        Err(DecodeAllError::Truncated { decoded: 10, expected: 16 })
+    ));
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes[..(bytes.len() - 8)])
+        .decode_header()
+        .unwrap();
+    assert!(matches!(
+        decoder.decode_bytes_vec(Pixel::rgb),
+        Err(DecodeAllError::Truncated { decoded: 10, expected: 16 })
+    ));
+}
+
+#[test]
+fn empty_image() {
+    for &(width, height) in &[(0, 0), (0, 5), (5, 0)] {
+        let bytes = qoi_bytes(width, height, 4, &[]);
+
+        assert!(matches!(
+            Decoder::new_from_slice(&bytes).decode_header(),
+            Err(HeaderDecodeError::EmptyImage)
+        ));
+
+        let (_header, decoder) = Decoder::new_from_slice(&bytes)
+            .with_options(DecoderOptions::new().allow_empty(true))
+            .decode_header()
+            .unwrap();
+        assert_eq!(decoder.decode_pixels_vec().unwrap(), []);
+    }
+}
+
+#[test]
+fn decode_capped() {
+    // The header claims far more pixels than there are, but the stream ends after 10
+    let bytes = qoi_bytes(100_000, 100_000, 4, &[0xFE, 10, 20, 30, 0xC8]);
+    let bytes = &bytes[..(bytes.len() - 8)];
+    let pixel = Pixel::new(10, 20, 30, 255);
+
+    let (_header, mut decoder) = Decoder::new_from_slice(bytes).decode_header().unwrap();
+    let mut buf = [Pixel::ZERO; 4];
+    assert_eq!(decoder.decode_capped(&mut buf).unwrap(), 4);
+    assert_eq!(buf, [pixel; 4]);
+
+    let mut buf = [Pixel::ZERO; 64];
+    assert_eq!(decoder.decode_capped(&mut buf).unwrap(), 6);
+    assert_eq!(buf[..6], [pixel; 6]);
+    assert_eq!(decoder.decode_capped(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn byte_reader() {
+    let pixels = (0..500u32)
+        .map(|i| Pixel::new(i as u8, (i / 3) as u8, 9, (i % 2) as u8 * 255))
+        .collect::<Vec<_>>();
+    let header = Header::new(25, 20, Channels::Rgba, ColSpace::Srgb);
+    let bytes = Encoder::encode_to_vec(header, &pixels).unwrap();
+    let expected = pixels.iter().flat_map(|pixel| pixel.rgb()).collect::<Vec<_>>();
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let mut out = Vec::new();
+    io::copy(&mut decoder.into_byte_reader(Pixel::rgb), &mut out).unwrap();
+    assert_eq!(out, expected);
+
+    // Buffers which are not a multiple of the pixel size split pixels between reads
+    for &len in &[1, 2, 4, 7] {
+        let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+        let mut reader = decoder.into_byte_reader(Pixel::rgb);
+        let mut out = Vec::new();
+        let mut buf = vec![0; len];
+        loop {
+            match reader.read(&mut buf).unwrap() {
+                0 => break,
+                n => out.extend_from_slice(&buf[..n]),
+            }
+        }
+        assert_eq!(out, expected);
+    }
+}
+
+#[test]
+fn dyn_reader() {
+    let bytes = qoi_bytes(3, 1, 4, &[0xFE, 10, 20, 30, 0xC1]);
+    let mut reader: Box<dyn Read> = Box::new(io::Cursor::new(bytes));
+
+    let (_header, decoder) = Decoder::new_from_dyn_reader(&mut *reader).decode_header().unwrap();
+    assert_eq!(decoder.decode_pixels_vec().unwrap(), [Pixel::new(10, 20, 30, 255); 3]);
+}
+
+#[test]
+fn decode_palette() {
+    // Two pixels, then a run of 5 of the second, then the first again from the index
+    let red = Pixel::new(255, 0, 0, 255);
+    let blue = Pixel::new(0, 0, 255, 255);
+    let bytes = qoi_bytes(8, 1, 4, &[0xFE, 255, 0, 0, 0xFE, 0, 0, 255, 0xC4, red.qoi_hash()]);
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let palette = decoder.decode_palette(2).unwrap().unwrap();
+    assert_eq!(palette.len(), 2);
+    assert_eq!(palette[&red], 2);
+    assert_eq!(palette[&blue], 6);
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert!(decoder.decode_palette(1).unwrap().is_none());
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes[..18]).decode_header().unwrap();
+    assert!(matches!(
+        decoder.decode_palette(2),
+        Err(DecodeAllError::Truncated { decoded: 1, expected: 8 })
+    ));
+}
+
+#[test]
+fn into_stream() {
+    let mut bytes = qoi_bytes(3, 1, 4, &[0xFE, 10, 20, 30, 0xC1]);
+    bytes.extend_from_slice(b"trailing");
+
+    let (_header, mut decoder) = Decoder::new_from_reader(&bytes[..]).decode_header().unwrap();
+    let mut pixels = [Pixel::ZERO; 3];
+    assert_eq!(decoder.decode_pixels_into(&mut pixels).unwrap(), (3, true));
+    assert_eq!(decoder.stream_mut().read_n().unwrap(), Header::END_MARKER);
+
+    let mut rest = Vec::new();
+    decoder.into_stream().into_inner().read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"trailing");
+}
+
+#[test]
+fn strict_unwritten_index() {
+    // The second chunk refers to slot 5, which nothing has been written to
+    let bytes = qoi_bytes(3, 1, 4, &[0xFE, 1, 2, 3, 0x05, 0x00]);
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(decoder.decode_pixels_vec().unwrap()[1], Pixel::ZERO);
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).strict().decode_header().unwrap();
+    assert!(matches!(
+        decoder.decode_pixels_vec(),
+        Err(DecodeAllError::SuspiciousStream { offset: Some(18) })
+    ));
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).strict().decode_header().unwrap();
+    assert!(matches!(
+        decoder.decode_bytes_vec(Pixel::rgba),
+        Err(DecodeAllError::SuspiciousStream { offset: Some(18) })
+    ));
+
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).strict().decode_header().unwrap();
+    assert!(matches!(
+        decoder.decode_pixels_arc(),
+        Err(DecodeAllError::SuspiciousStream { offset: Some(18) })
+    ));
+
+    let (header, decoder) = Decoder::new_from_slice(&bytes).strict().decode_header().unwrap();
+    assert!(matches!(
+        decoder.decode_pixels_vec_flipped(&header),
+        Err(DecodeAllError::SuspiciousStream { offset: Some(18) })
+    ));
+
+    let (_header, mut decoder) = Decoder::new_from_slice(&bytes).strict().decode_header().unwrap();
+    let mut pixels = [Pixel::BLACK; 3];
+    decoder.decode_pixels_into(&mut pixels).unwrap();
+    assert_eq!(decoder.suspicious_offset(), Some(18));
+
+    // Slot 0 can legitimately be referred to before it is written
+    let bytes = qoi_bytes(2, 1, 4, &[0x00, 0xFE, 1, 2, 3]);
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).strict().decode_header().unwrap();
+    assert_eq!(decoder.decode_pixels_vec().unwrap(), [Pixel::ZERO, Pixel::new(1, 2, 3, 0)]);
+}
+
+#[test]
+fn non_canonical_index_matches_qoi_crate() {
+    let translucent = Pixel::new(0, 0, 0, 64);
+    let cases: [(&[u8], &[Pixel]); 3] = [
+        // Slot 5 has never been written, so it gives `Pixel::ZERO`, which is not inserted over the
+        // translucent black already in slot 0
+        (
+            &[0xFF, 0, 0, 0, 64, 0x05, 0x00],
+            &[translucent, Pixel::ZERO, translucent],
+        ),
+        // A run of the `Pixel::ZERO` from an unwritten slot does not insert it into slot 0 either
+        (
+            &[0xFF, 0, 0, 0, 64, 0x05, 0xC0, 0x00],
+            &[translucent, Pixel::ZERO, Pixel::ZERO, translucent],
+        ),
+        // The initial black pixel repeated by a leading run is not inserted into slot 53
+        (&[0xC0, 0x35], &[Pixel::BLACK, Pixel::ZERO]),
+    ];
+
+    for (chunks, expected) in cases {
+        let bytes = qoi_bytes(expected.len() as u32, 1, 4, chunks);
+        let pixels = decode(&bytes);
+        assert_eq!(pixels, expected);
+
+        let (_header, rgba) = qoi::decode_to_vec(&bytes).unwrap();
+        let reference = rgba
+            .chunks_exact(4)
+            .map(|px| Pixel::new(px[0], px[1], px[2], px[3]))
+            .collect::<Vec<_>>();
+        assert_eq!(pixels, reference);
+    }
+}
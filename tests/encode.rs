@@ -0,0 +1,184 @@
+use okay::encode::EncodeError;
+use okay::header::{Channels, ColSpace};
+use okay::{Decoder, Encoder, Header, Pixel};
+
+fn encode(header: Header, pixels: &[Pixel]) -> Vec<u8> {
+    Encoder::encode_to_vec(header, pixels).unwrap()
+}
+
+/// Returns the chunks of an encoded image, between the header and the end marker.
+fn chunks(bytes: &[u8]) -> &[u8] {
+    &bytes[14..(bytes.len() - 8)]
+}
+
+fn decode(bytes: &[u8]) -> (Header, Vec<Pixel>) {
+    let (header, decoder) = Decoder::new_from_slice(bytes).decode_header().unwrap();
+    (header, decoder.decode_pixels_vec().unwrap())
+}
+
+/// Pixels which exercise every kind of chunk other than runs.
+fn varied_pixels(len: usize) -> Vec<Pixel> {
+    let mut state = 0x2545F491u32;
+    (0..len)
+        .map(|i| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let [r, g, b, a] = state.to_le_bytes();
+            match i % 5 {
+                0 => Pixel::new(r, g, b, a),
+                1 => Pixel::new(r, g, b, 255),
+                2 => Pixel::new(r & 3, g & 3, b & 3, 255),
+                3 => Pixel::new(g.wrapping_add(r & 7), g, g.wrapping_sub(b & 7), 255),
+                _ => Pixel::new(10, 20, 30, 40),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn writes_header() {
+    let header = Header::new(0x01020304, 0, Channels::Rgba, ColSpace::Linear);
+    let bytes = encode(header, &[]);
+    assert_eq!(
+        bytes,
+        [b'q', b'o', b'i', b'f', 1, 2, 3, 4, 0, 0, 0, 0, 4, 1, 0, 0, 0, 0, 0, 0, 0, 1]
+    );
+}
+
+#[test]
+fn chooses_chunks() {
+    let header = Header::new(5, 1, Channels::Rgba, ColSpace::Srgb);
+    let pixels = [
+        Pixel::new(1, 0, 255, 255),
+        Pixel::new(11, 10, 5, 255),
+        Pixel::new(100, 150, 200, 255),
+        Pixel::new(100, 150, 200, 7),
+        Pixel::new(1, 0, 255, 255),
+    ];
+
+    let bytes = encode(header, &pixels);
+    assert_eq!(
+        chunks(&bytes),
+        [
+            0x40 | (3 << 4) | (2 << 2) | 1,
+            0x80 | 42,
+            0x84,
+            0xFE, 100, 150, 200,
+            0xFF, 100, 150, 200, 7,
+            // The hash of (1, 0, 255, 255)
+            49,
+        ]
+    );
+}
+
+#[test]
+fn splits_long_runs() {
+    let header = Header::new(65, 1, Channels::Rgba, ColSpace::Srgb);
+    let bytes = encode(header, &[Pixel::BLACK; 65]);
+    assert_eq!(chunks(&bytes), [0xC0 | 61, 0xC0 | 2]);
+}
+
+#[test]
+fn run_then_new_pixel() {
+    let header = Header::new(4, 1, Channels::Rgba, ColSpace::Srgb);
+    let pixel = Pixel::new(100, 150, 200, 255);
+    let bytes = encode(header, &[pixel, pixel, pixel, Pixel::BLACK]);
+    assert_eq!(chunks(&bytes), [0xFE, 100, 150, 200, 0xC0 | 1, 0xFE, 0, 0, 0]);
+}
+
+#[test]
+fn round_trip_flat() {
+    let header = Header::new(100, 10, Channels::Rgba, ColSpace::Srgb);
+    let pixels = vec![Pixel::new(100, 150, 200, 255); 1000];
+
+    let bytes = encode(header, &pixels);
+    // One QOI_OP_RGB chunk, then 999 repeats in 16 maximum-length runs and one run of 7
+    assert_eq!(chunks(&bytes).len(), 4 + 17);
+    assert_eq!(decode(&bytes).1, pixels);
+}
+
+#[test]
+fn writes_end_marker() {
+    let header = Header::new(10, 10, Channels::Rgba, ColSpace::Srgb);
+    let bytes = encode(header, &varied_pixels(100));
+    assert_eq!(bytes[(bytes.len() - 8)..], [0, 0, 0, 0, 0, 0, 0, 1]);
+}
+
+#[test]
+fn round_trip() {
+    let header = Header::new(40, 25, Channels::Rgba, ColSpace::Srgb);
+    let pixels = varied_pixels(1000);
+    let bytes = encode(header.clone(), &pixels);
+
+    let (decoded_header, decoded) = decode(&bytes);
+    assert_eq!(decoded_header, header);
+    assert_eq!(decoded, pixels);
+
+    let (_, reference) = qoi::decode_to_vec(&bytes).unwrap();
+    let pixels_bytes = pixels.iter().flat_map(|pixel| pixel.rgba()).collect::<Vec<_>>();
+    assert_eq!(reference, pixels_bytes);
+}
+
+#[test]
+fn rgb_ignores_alpha() {
+    let header = Header::new(2, 1, Channels::Rgb, ColSpace::Srgb);
+    let pixels = [Pixel::new(100, 150, 200, 0), Pixel::new(100, 150, 201, 50)];
+
+    let (_, decoded) = decode(&encode(header, &pixels));
+    assert_eq!(
+        decoded,
+        [Pixel::new(100, 150, 200, 255), Pixel::new(100, 150, 201, 255)]
+    );
+}
+
+#[test]
+fn to_writer() {
+    let header = Header::new(10, 10, Channels::Rgba, ColSpace::Srgb);
+    let pixels = varied_pixels(100);
+
+    let mut encoder = Encoder::new_to_writer(Vec::new(), header.clone())
+        .encode_header()
+        .unwrap();
+    encoder.encode_pixels(&pixels[..30]).unwrap();
+    encoder.encode_pixels(&pixels[30..]).unwrap();
+    let bytes = encoder.finish().unwrap().into_inner();
+
+    assert_eq!(bytes, encode(header, &pixels));
+}
+
+#[test]
+fn wrong_pixel_count() {
+    let header = Header::new(2, 2, Channels::Rgba, ColSpace::Srgb);
+
+    let mut encoder = Encoder::new_to_vec(header.clone()).encode_header().unwrap();
+    assert!(matches!(
+        encoder.encode_pixels(&[Pixel::BLACK; 5]),
+        Err(EncodeError::TooManyPixels)
+    ));
+    assert_eq!(encoder.remaining_pixels(), 4);
+
+    encoder.encode_pixels(&[Pixel::BLACK; 3]).unwrap();
+    assert!(matches!(
+        encoder.finish(),
+        Err(EncodeError::TooFewPixels { missing: 1 })
+    ));
+}
+
+#[test]
+fn encode_iter() {
+    let header = Header::new(40, 25, Channels::Rgba, ColSpace::Srgb);
+    let pixels = varied_pixels(1000);
+
+    let bytes = Encoder::encode_iter_to_vec(header.clone(), pixels.iter().copied()).unwrap();
+    assert_eq!(bytes, encode(header.clone(), &pixels));
+
+    assert!(matches!(
+        Encoder::encode_iter_to_vec(header.clone(), pixels.iter().copied().take(999)),
+        Err(EncodeError::TooFewPixels { missing: 1 })
+    ));
+    assert!(matches!(
+        Encoder::encode_iter_to_vec(header, pixels.iter().copied().cycle()),
+        Err(EncodeError::TooManyPixels)
+    ));
+}
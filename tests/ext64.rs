@@ -0,0 +1,76 @@
+#![cfg(feature = "ext64")]
+
+use okay::byte_sink::VecByteSink;
+use okay::decode::HeaderDecodeError;
+use okay::ext64::Header64;
+use okay::header::{Channels, ColSpace};
+use okay::{Decoder, DecoderOptions, Encoder, Header, Pixel};
+
+fn encode_ext64(header: &Header64, pixels: &[Pixel]) -> Vec<u8> {
+    let sink = VecByteSink::new(Vec::new());
+    let mut encoder = Encoder::new_ext64(sink, header.clone()).encode_header().unwrap();
+    encoder.encode_pixels(pixels).unwrap();
+    encoder.finish().unwrap().into_inner()
+}
+
+#[test]
+fn ext64_round_trip() {
+    let header = Header64::new(6, 5, Channels::Rgba, ColSpace::Linear);
+    let pixels = (0..30u8)
+        .map(|i| Pixel::new(i * 8, i / 3, 200 - i, 255 - (i % 4) * 20))
+        .collect::<Vec<_>>();
+    let bytes = encode_ext64(&header, &pixels);
+
+    assert_eq!(&bytes[..4], b"qoi8");
+    assert_eq!(&bytes[..22], &header.to_bytes());
+
+    // Apart from the header, the file is the same as a standard one
+    let standard = Encoder::encode_to_vec(header.to_standard().unwrap(), &pixels).unwrap();
+    assert_eq!(&bytes[22..], &standard[14..]);
+
+    let (decoded_header, decoder) = Decoder::new_from_slice(&bytes).decode_header_ext64().unwrap();
+    assert_eq!(decoded_header, header);
+    assert_eq!(decoder.decode_pixels_vec_checked().unwrap(), pixels);
+}
+
+#[test]
+fn ext64_decodes_standard_header() {
+    let header = Header::new(3, 2, Channels::Rgb, ColSpace::Srgb);
+    let pixels = (0..6u8).map(|i| Pixel::new(i, i * 2, i * 3, 255)).collect::<Vec<_>>();
+    let bytes = Encoder::encode_to_vec(header.clone(), &pixels).unwrap();
+
+    let (decoded_header, decoder) = Decoder::new_from_slice(&bytes).decode_header_ext64().unwrap();
+    assert_eq!(decoded_header, Header64::from(header));
+    assert_eq!(decoder.decode_pixels_vec_checked().unwrap(), pixels);
+}
+
+#[test]
+fn ext64_large_dimensions() {
+    let width = u64::from(u32::MAX) + 1;
+    let header = Header64::new(width, 1, Channels::Rgb, ColSpace::Srgb);
+    assert_eq!(header.to_standard(), None);
+
+    let bytes = header.to_bytes();
+
+    let (decoded_header, decoder) = Decoder::new_from_slice(&bytes).decode_header_ext64().unwrap();
+    assert_eq!(decoded_header, header);
+    assert_eq!(decoder.remaining_pixels(), width);
+
+    // Pixel limits still apply to extended headers
+    let options = DecoderOptions::new().max_pixels(u64::from(u32::MAX));
+    match Decoder::new_from_slice(&bytes).with_options(options).decode_header_ext64() {
+        Err(HeaderDecodeError::TooLarge { .. }) => (),
+        res => panic!("expected TooLarge, got {:?}", res.map(|(header, _)| header)),
+    }
+}
+
+#[test]
+fn standard_decoder_rejects_ext64() {
+    let header = Header64::new(1, 1, Channels::Rgba, ColSpace::Srgb);
+    let bytes = encode_ext64(&header, &[Pixel::BLACK]);
+
+    assert!(matches!(
+        Decoder::new_from_slice(&bytes).decode_header(),
+        Err(HeaderDecodeError::Magic(_))
+    ));
+}
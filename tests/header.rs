@@ -0,0 +1,136 @@
+use std::io::{self, Read};
+
+use okay::header::{Channels, ColSpace};
+use okay::{Decoder, DecoderOptions, Header, Pixel};
+
+#[test]
+fn channels_from_str() {
+    assert_eq!("rgb".parse::<Channels>().unwrap(), Channels::Rgb);
+    assert_eq!("RGBA".parse::<Channels>().unwrap(), Channels::Rgba);
+    assert_eq!("rGbA".parse::<Channels>().unwrap(), Channels::Rgba);
+    assert_eq!("rgbx".parse::<Channels>().unwrap_err().bad_value(), "rgbx");
+}
+
+#[test]
+fn to_bytes_round_trip() {
+    let headers = [
+        Header::new(640, 480, Channels::Rgba, ColSpace::Srgb),
+        Header::new(1, u32::MAX, Channels::Rgb, ColSpace::Linear),
+        Header::new(0, 0, Channels::Rgb, ColSpace::Srgb),
+    ];
+
+    for header in &headers {
+        let bytes = header.to_bytes();
+        let (decoded, _) = Decoder::new_from_slice(&bytes)
+            .with_options(DecoderOptions::new().allow_empty(true))
+            .decode_header()
+            .unwrap();
+        assert_eq!(&decoded, header);
+    }
+
+    assert_eq!(
+        headers[0].to_bytes(),
+        [b'q', b'o', b'i', b'f', 0, 0, 2, 128, 0, 0, 1, 224, 4, 0]
+    );
+}
+
+#[test]
+fn header_display() {
+    let header = Header::new(640, 480, Channels::Rgba, ColSpace::Srgb);
+    assert_eq!(header.to_string(), "640x480 RGBA sRGB");
+
+    let header = Header::new(1, 2, Channels::Rgb, ColSpace::Linear);
+    assert_eq!(header.to_string(), "1x2 RGB linear");
+}
+
+#[test]
+fn num_channels() {
+    assert_eq!(Channels::Rgb.num_channels(), 3);
+    assert_eq!(Channels::Rgba.num_channels(), 4);
+}
+
+#[test]
+fn decoded_len() {
+    assert_eq!(Header::new(640, 480, Channels::Rgba, ColSpace::Srgb).decoded_len(), Some(1228800));
+    assert_eq!(Header::new(640, 480, Channels::Rgb, ColSpace::Srgb).decoded_len(), Some(921600));
+    assert_eq!(Header::new(0, 480, Channels::Rgb, ColSpace::Srgb).decoded_len(), Some(0));
+
+    let huge = Header::new(u32::MAX, u32::MAX, Channels::Rgba, ColSpace::Srgb);
+    assert_eq!(huge.decoded_len(), None);
+}
+
+#[test]
+fn detect_channels() {
+    assert_eq!(Channels::detect(&[]), Channels::Rgb);
+    assert_eq!(
+        Channels::detect(&[Pixel::BLACK, Pixel::new(1, 2, 3, 255)]),
+        Channels::Rgb
+    );
+    assert_eq!(
+        Channels::detect(&[Pixel::BLACK, Pixel::new(1, 2, 3, 254)]),
+        Channels::Rgba
+    );
+}
+
+#[test]
+fn col_space_from_str() {
+    assert_eq!("srgb".parse::<ColSpace>().unwrap(), ColSpace::Srgb);
+    assert_eq!("Linear".parse::<ColSpace>().unwrap(), ColSpace::Linear);
+    assert_eq!("".parse::<ColSpace>().unwrap_err().bad_value(), "");
+}
+
+#[test]
+fn display_round_trips() {
+    for channels in [Channels::Rgb, Channels::Rgba] {
+        assert_eq!(channels.to_string().parse::<Channels>().unwrap(), channels);
+    }
+    for col_space in [ColSpace::Srgb, ColSpace::Linear] {
+        assert_eq!(col_space.to_string().parse::<ColSpace>().unwrap(), col_space);
+    }
+}
+
+fn magic_error_message(bytes: &[u8]) -> String {
+    match Decoder::new_from_slice(bytes).decode_header() {
+        Ok(_) => panic!("expected bad magic bytes"),
+        Err(err) => err.to_string(),
+    }
+}
+
+#[test]
+fn magic_error_hints() {
+    assert_eq!(
+        magic_error_message(&[0; 14]),
+        "bad magic bytes 00000000, expected 716F6966 (input appears to be empty or zeroed)"
+    );
+    assert!(magic_error_message(b"\x89PNG\r\n\x1a\n").ends_with("(input appears to be a PNG file)"));
+    assert!(magic_error_message(&[0xFF, 0xD8, 0xFF, 0xE0]).ends_with("(input appears to be a JPEG file)"));
+    assert!(magic_error_message(b"GIF89a").ends_with(", expected 716F6966"));
+}
+
+#[test]
+fn try_decode_header_does_not_consume_other_formats() {
+    let png = b"\x89PNG\r\n\x1a\n more png data".to_vec();
+    let reader = io::BufReader::new(&png[..]);
+
+    let decoder = match Decoder::new_from_reader(reader).try_decode_header() {
+        Ok(_) => panic!("PNG accepted as QOI"),
+        Err(decoder) => decoder,
+    };
+
+    let mut remaining = Vec::new();
+    decoder.into_stream().into_inner().read_to_end(&mut remaining).unwrap();
+    assert_eq!(remaining, png);
+}
+
+#[test]
+fn try_decode_header_accepts_qoi() {
+    let bytes = qoi::encode_to_vec([1, 2, 3, 4], 1, 1).unwrap();
+    let reader = io::BufReader::new(&bytes[..]);
+
+    let (header, _decoder) = Decoder::new_from_reader(reader)
+        .try_decode_header()
+        .ok()
+        .unwrap()
+        .unwrap();
+    assert_eq!(header.dimensions(), (1, 1));
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "image")]
+
+use image::{ColorType, DynamicImage, ImageDecoder};
+
+use okay::header::{Channels, ColSpace};
+use okay::image::QoiDecoder;
+use okay::{Decoder, Encoder, Header, Pixel};
+
+fn gradient() -> Vec<Pixel> {
+    (0..=255u8).map(|i| Pixel::new(i, i / 2, 255 - i, i | 1)).collect()
+}
+
+#[test]
+fn dynamic_image_rgba() {
+    let pixels = gradient();
+    let bytes = Encoder::encode_to_vec(
+        Header::new(32, 8, Channels::Rgba, ColSpace::Srgb),
+        &pixels,
+    )
+    .unwrap();
+
+    let decoder = QoiDecoder::new(Decoder::new_from_slice(&bytes)).unwrap();
+    assert_eq!(decoder.dimensions(), (32, 8));
+    assert_eq!(decoder.color_type(), ColorType::Rgba8);
+
+    let image = DynamicImage::from_decoder(decoder).unwrap().into_rgba8();
+    assert_eq!(image.dimensions(), (32, 8));
+    assert_eq!(
+        image.into_raw(),
+        pixels.iter().flat_map(|pixel| pixel.rgba()).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn dynamic_image_rgb() {
+    let pixels = gradient();
+    let bytes = Encoder::encode_to_vec(
+        Header::new(16, 16, Channels::Rgb, ColSpace::Srgb),
+        &pixels,
+    )
+    .unwrap();
+
+    let decoder = QoiDecoder::from_reader(&bytes[..]).unwrap();
+    assert_eq!(decoder.color_type(), ColorType::Rgb8);
+
+    let image = DynamicImage::from_decoder(decoder).unwrap();
+    assert_eq!(
+        image.into_rgb8().into_raw(),
+        pixels.iter().flat_map(|pixel| pixel.rgb()).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn bad_magic() {
+    assert!(QoiDecoder::new(Decoder::new_from_slice(b"\x89PNG\r\n\x1a\n000000")).is_err());
+}
+
+#[test]
+fn encode_dynamic_image() {
+    let pixels = gradient();
+    let rgba = pixels.iter().flat_map(|pixel| pixel.rgba()).collect::<Vec<_>>();
+    let image = DynamicImage::ImageRgba8(image::RgbaImage::from_raw(32, 8, rgba).unwrap());
+
+    let bytes = okay::encode_dynamic_image(&image, ColSpace::Linear).unwrap();
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(header, Header::new(32, 8, Channels::Rgba, ColSpace::Linear));
+    assert_eq!(decoder.decode_pixels_vec().unwrap(), pixels);
+
+    let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+    let bytes = okay::encode_dynamic_image(&rgb, ColSpace::Srgb).unwrap();
+    let (header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    assert_eq!(header.channels(), Channels::Rgb);
+    assert_eq!(
+        decoder.decode_pixels_vec().unwrap(),
+        pixels.iter().map(|pixel| Pixel::from(pixel.rgb())).collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "memmap")]
+
+use std::fs;
+
+use okay::byte_stream::MmapByteStream;
+use okay::header::{Channels, ColSpace};
+use okay::{Decoder, Encoder, Header, Pixel};
+
+#[test]
+fn decode_mmap() {
+    let header = Header::new(16, 16, Channels::Rgba, ColSpace::Srgb);
+    let pixels = (0..=255u8)
+        .map(|i| Pixel::new(i, i / 2, 255 - i, 255))
+        .collect::<Vec<_>>();
+    let bytes = Encoder::encode_to_vec(header.clone(), &pixels).unwrap();
+
+    let path = std::env::temp_dir().join(format!("okay_mmap_{}.qoi", std::process::id()));
+    fs::write(&path, &bytes).unwrap();
+
+    // SAFETY:
+    // The file is private to this test and is not touched again until the decoder is done with it
+    let stream = unsafe { MmapByteStream::open(&path) }.unwrap();
+    let (decoded_header, decoder) = Decoder::new(stream).decode_header().unwrap();
+    let decoded = decoder.decode_pixels_vec_checked();
+
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(decoded_header, header);
+    assert_eq!(decoded.unwrap(), pixels);
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "rayon")]
+
+use okay::header::{Channels, ColSpace};
+use okay::parallel::{decode_tiles_parallel, TileDecodeError};
+use okay::{Encoder, Header, Pixel};
+
+fn tile(seed: u8) -> (Vec<Pixel>, Vec<u8>) {
+    let pixels = (0..64u8)
+        .map(|i| Pixel::new(i.wrapping_mul(seed), seed, i / 8, 255))
+        .collect::<Vec<_>>();
+    let header = Header::new(8, 8, Channels::Rgba, ColSpace::Srgb);
+    let bytes = Encoder::encode_to_vec(header, &pixels).unwrap();
+    (pixels, bytes)
+}
+
+#[test]
+fn decodes_in_order() {
+    let (pixels, bytes): (Vec<_>, Vec<_>) = (1..=20).map(tile).unzip();
+    let streams = bytes.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+    assert_eq!(decode_tiles_parallel(&streams).unwrap(), pixels);
+}
+
+#[test]
+fn reports_first_bad_tile() {
+    let (_, bytes): (Vec<_>, Vec<_>) = (1..=5).map(tile).unzip();
+    let mut streams = bytes.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    streams[1] = &bytes[1][..20];
+    streams[3] = b"not qoi at all";
+
+    match decode_tiles_parallel(&streams) {
+        Err(err @ TileDecodeError::Pixels { .. }) => assert_eq!(err.tile(), 1),
+        res => panic!("expected tile 1 to fail, got {:?}", res.map(|_| ())),
+    }
+}
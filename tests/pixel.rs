@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use okay::Pixel;
+
+#[test]
+fn rgb565_extremes() {
+    assert_eq!(Pixel::new(0, 0, 0, 0).rgb565(), [0x00, 0x00]);
+    assert_eq!(Pixel::new(255, 255, 255, 0).rgb565(), [0xFF, 0xFF]);
+    assert_eq!(Pixel::new(255, 0, 0, 255).rgb565(), [0xF8, 0x00]);
+    assert_eq!(Pixel::new(0, 255, 0, 255).rgb565(), [0x07, 0xE0]);
+    assert_eq!(Pixel::new(0, 0, 255, 255).rgb565(), [0x00, 0x1F]);
+}
+
+#[test]
+fn rgb565_is_big_endian() {
+    let packed = Pixel::new(255, 0, 0, 255).rgb565();
+    assert_eq!(u16::from_be_bytes(packed), 0xF800);
+}
+
+#[test]
+fn rgb565_rounds() {
+    // 127 * 31 / 255 = 15.45, 128 * 31 / 255 = 15.56
+    assert_eq!(u16::from_be_bytes(Pixel::new(127, 0, 0, 255).rgb565()) >> 11, 15);
+    assert_eq!(u16::from_be_bytes(Pixel::new(128, 0, 0, 255).rgb565()) >> 11, 16);
+}
+
+#[test]
+fn rgba4444_extremes() {
+    assert_eq!(Pixel::new(0, 0, 0, 0).rgba4444(), [0x00, 0x00]);
+    assert_eq!(Pixel::new(255, 255, 255, 255).rgba4444(), [0xFF, 0xFF]);
+    assert_eq!(Pixel::new(255, 0, 0, 0).rgba4444(), [0xF0, 0x00]);
+    assert_eq!(Pixel::new(0, 255, 0, 0).rgba4444(), [0x0F, 0x00]);
+    assert_eq!(Pixel::new(0, 0, 255, 0).rgba4444(), [0x00, 0xF0]);
+    assert_eq!(Pixel::new(0, 0, 0, 255).rgba4444(), [0x00, 0x0F]);
+}
+
+#[test]
+fn from_planes() {
+    let pixels = Pixel::from_planes(&[1, 2], &[3, 4], &[5, 6], &[7, 8]).unwrap();
+    assert_eq!(pixels, [Pixel::new(1, 3, 5, 7), Pixel::new(2, 4, 6, 8)]);
+
+    let pixels = Pixel::from_rgb_planes(&[1, 2], &[3, 4], &[5, 6]).unwrap();
+    assert_eq!(pixels, [Pixel::new(1, 3, 5, 255), Pixel::new(2, 4, 6, 255)]);
+}
+
+#[test]
+fn from_planes_length_mismatch() {
+    assert!(Pixel::from_planes(&[1, 2], &[3, 4], &[5], &[7, 8]).is_none());
+    assert!(Pixel::from_rgb_planes(&[1, 2], &[3, 4, 5], &[5, 6]).is_none());
+}
+
+#[test]
+fn srgb_linear_endpoints() {
+    let black = Pixel::new(0, 0, 0, 12);
+    let white = Pixel::new(255, 255, 255, 34);
+    assert_eq!(black.srgb_to_linear(), black);
+    assert_eq!(white.srgb_to_linear(), white);
+    assert_eq!(black.linear_to_srgb(), black);
+    assert_eq!(white.linear_to_srgb(), white);
+}
+
+#[test]
+fn srgb_linear_midpoint() {
+    // sRGB 50% grey is roughly 21.4% linear intensity
+    assert_eq!(Pixel::new(128, 128, 128, 255).srgb_to_linear(), Pixel::new(55, 55, 55, 255));
+    assert_eq!(Pixel::new(55, 55, 55, 255).linear_to_srgb(), Pixel::new(128, 128, 128, 255));
+}
+
+#[test]
+fn f32_normalized_endpoints() {
+    assert_eq!(Pixel::new(0, 0, 0, 0).to_f32_normalized(), [0.0; 4]);
+    assert_eq!(Pixel::new(255, 255, 255, 255).to_f32_normalized(), [1.0; 4]);
+    assert_eq!(Pixel::from_f32_normalized([0.0; 4]), Pixel::new(0, 0, 0, 0));
+    assert_eq!(Pixel::from_f32_normalized([1.0; 4]), Pixel::new(255, 255, 255, 255));
+}
+
+#[test]
+fn f32_normalized_round_trip() {
+    for c in 0..=255 {
+        let pixel = Pixel::new(c, 255 - c, c / 2, c);
+        assert_eq!(Pixel::from_f32_normalized(pixel.to_f32_normalized()), pixel);
+    }
+}
+
+#[test]
+fn f32_normalized_clamps() {
+    assert_eq!(
+        Pixel::from_f32_normalized([-0.5, 1.5, f32::INFINITY, f32::NEG_INFINITY]),
+        Pixel::new(0, 255, 255, 0)
+    );
+    assert_eq!(Pixel::from_f32_normalized([f32::NAN, 0.5, 0.0, 1.0]), Pixel::new(0, 128, 0, 255));
+}
+
+#[test]
+fn u32_conversions() {
+    let pixel = Pixel::new(0x12, 0x34, 0x56, 0x78);
+    assert_eq!(pixel.to_rgba_u32(), 0x12345678);
+    assert_eq!(pixel.to_argb_u32(), 0x78123456);
+    assert_eq!(Pixel::from_rgba_u32(0x12345678), pixel);
+    assert_eq!(Pixel::from_argb_u32(0x78123456), pixel);
+}
+
+#[test]
+fn array_conversions() {
+    assert_eq!(Pixel::from([1, 2, 3, 4]), Pixel::new(1, 2, 3, 4));
+    assert_eq!(Pixel::from([1, 2, 3]), Pixel::new(1, 2, 3, 255));
+    assert_eq!(<[u8; 4]>::from(Pixel::new(1, 2, 3, 4)), [1, 2, 3, 4]);
+
+    let pixels = [[1, 2, 3, 4], [5, 6, 7, 8]]
+        .into_iter()
+        .map(Pixel::from)
+        .collect::<Vec<_>>();
+    assert_eq!(pixels, [Pixel::new(1, 2, 3, 4), Pixel::new(5, 6, 7, 8)]);
+}
+
+#[test]
+fn premultiply() {
+    assert_eq!(Pixel::new(200, 100, 1, 255).premultiplied(), Pixel::new(200, 100, 1, 255));
+    assert_eq!(Pixel::new(200, 100, 1, 0).premultiplied(), Pixel::new(0, 0, 0, 0));
+    // 200 * 128 / 255 = 100.39, 100 * 128 / 255 = 50.2, 1 * 128 / 255 = 0.502
+    assert_eq!(Pixel::new(200, 100, 1, 128).premultiplied(), Pixel::new(100, 50, 1, 128));
+}
+
+#[test]
+fn unpremultiply() {
+    assert_eq!(Pixel::new(100, 50, 1, 128).unpremultiplied(), Pixel::new(199, 100, 2, 128));
+    assert_eq!(Pixel::new(0, 0, 0, 0).unpremultiplied(), Pixel::new(0, 0, 0, 0));
+    assert_eq!(Pixel::new(200, 0, 0, 100).unpremultiplied(), Pixel::new(255, 0, 0, 100));
+
+    // Premultiplying and unpremultiplying is exact when alpha is 255
+    for c in 0..=255 {
+        let pixel = Pixel::new(c, c, c, 255);
+        assert_eq!(pixel.premultiplied().unpremultiplied(), pixel);
+    }
+}
+
+#[test]
+fn over() {
+    let background = Pixel::new(10, 20, 30, 255);
+    let translucent_bg = Pixel::new(10, 20, 30, 100);
+
+    assert_eq!(Pixel::new(1, 2, 3, 0).over(background), background);
+    assert_eq!(Pixel::new(1, 2, 3, 0).over(translucent_bg), translucent_bg);
+    assert_eq!(Pixel::new(1, 2, 3, 255).over(translucent_bg), Pixel::new(1, 2, 3, 255));
+
+    // Halfway between the two, and opaque because the background is
+    assert_eq!(
+        Pixel::new(210, 120, 30, 128).over(background),
+        Pixel::new(110, 70, 30, 255)
+    );
+
+    // Alpha is 128 + 100 * 127 / 255 = 177.8, and the colour is weighted towards the source by
+    // 128 * 255 to 100 * 127
+    assert_eq!(
+        Pixel::new(210, 120, 30, 128).over(translucent_bg),
+        Pixel::new(154, 92, 30, 178)
+    );
+
+    assert_eq!(Pixel::new(1, 2, 3, 128).over(Pixel::ZERO), Pixel::new(1, 2, 3, 128));
+}
+
+#[test]
+fn luminance() {
+    assert_eq!(Pixel::new(0, 0, 0, 255).luminance(), 0);
+    assert_eq!(Pixel::new(255, 255, 255, 0).luminance(), 255);
+    // 255 * 0.2126 = 54.2, 255 * 0.7152 = 182.4, 255 * 0.0722 = 18.4
+    assert_eq!(Pixel::new(255, 0, 0, 255).luminance(), 54);
+    assert_eq!(Pixel::new(0, 255, 0, 255).luminance(), 182);
+    assert_eq!(Pixel::new(0, 0, 255, 255).luminance(), 18);
+
+    for c in 0..=255 {
+        assert_eq!(Pixel::new(c, c, c, 255).luminance(), c);
+    }
+
+    assert_eq!(Pixel::new(255, 0, 0, 7).to_grayscale(), Pixel::new(54, 54, 54, 7));
+}
+
+#[test]
+fn bgr_order() {
+    let pixel = Pixel::new(1, 2, 3, 4);
+    assert_eq!(pixel.bgra(), [3, 2, 1, 4]);
+    assert_eq!(pixel.bgr(), [3, 2, 1]);
+}
+
+#[test]
+fn qoi_hash() {
+    assert_eq!(Pixel::ZERO.qoi_hash(), 0);
+    assert_eq!(Pixel::BLACK.qoi_hash(), (255 * 11 % 64) as u8);
+    assert_eq!(Pixel::new(255, 255, 255, 255).qoi_hash(), 38);
+    assert_eq!(Pixel::new(1, 0, 255, 255).qoi_hash(), 49);
+}
+
+#[test]
+fn default_and_hash() {
+    assert_eq!(Pixel::default(), Pixel::ZERO);
+
+    let mut counts = HashMap::new();
+    for pixel in [Pixel::BLACK, Pixel::ZERO, Pixel::BLACK, Pixel::new(0, 0, 0, 255)] {
+        *counts.entry(pixel).or_insert(0) += 1;
+    }
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts[&Pixel::BLACK], 3);
+}
+
+#[test]
+fn map_channels() {
+    let pixel = Pixel::new(0, 100, 255, 7);
+    assert_eq!(pixel.map_rgb(|c| 255 - c), Pixel::new(255, 155, 0, 7));
+    assert_eq!(pixel.map_all(|c| c / 2), Pixel::new(0, 50, 127, 3));
+    assert_eq!(pixel.map_all(|c| c), pixel);
+}
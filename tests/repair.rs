@@ -0,0 +1,50 @@
+use std::fs;
+use std::process::Command;
+
+use okay::header::{Channels, ColSpace};
+use okay::{Decoder, Encoder, Header, Pixel};
+
+#[test]
+fn repair_truncated_mid_chunk() {
+    let header = Header::new(4, 3, Channels::Rgba, ColSpace::Srgb);
+    let pixels = (0..12u8)
+        .map(|i| Pixel::new(i * 20, 255 - i * 20, i * 7, 250 - i * 10))
+        .collect::<Vec<_>>();
+    let bytes = Encoder::encode_to_vec(header, &pixels).unwrap();
+
+    // Every pixel has a different alpha, so each is a five-byte QOI_OP_RGBA chunk. Cut the file two
+    // bytes into the chunk for the ninth pixel
+    let chunks = &bytes[14..(bytes.len() - Header::END_MARKER.len())];
+    assert_eq!(chunks.len(), 12 * 5);
+    assert!(chunks.chunks(5).all(|chunk| chunk[0] == 0xFF));
+    let truncated = &bytes[..(14 + 8 * 5 + 2)];
+
+    let dir = std::env::temp_dir();
+    let in_path = dir.join(format!("okay_repair_in_{}.qoi", std::process::id()));
+    let out_path = dir.join(format!("okay_repair_out_{}.qoi", std::process::id()));
+    fs::write(&in_path, truncated).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_qoi_repair"))
+        .arg(&in_path)
+        .arg(&out_path)
+        .output()
+        .unwrap()
+        .status;
+    let repaired = fs::read(&out_path);
+
+    fs::remove_file(&in_path).unwrap();
+    let _ = fs::remove_file(&out_path);
+
+    assert!(status.success());
+    let repaired = repaired.unwrap();
+    assert_eq!(repaired.len(), 14 + 8 * 5 + Header::END_MARKER.len());
+    assert!(repaired.ends_with(&Header::END_MARKER));
+
+    let (repaired_header, decoder) = Decoder::new_from_slice(&repaired).decode_header().unwrap();
+    assert_eq!(repaired_header.dimensions(), (4, 2));
+    assert_eq!(decoder.decode_pixels_vec_checked().unwrap(), &pixels[..8]);
+
+    let (reference_header, reference) = qoi::decode_to_vec(&repaired).unwrap();
+    assert_eq!((reference_header.width, reference_header.height), (4, 2));
+    assert!(reference == pixels[..8].iter().flat_map(|pixel| pixel.rgba()).collect::<Vec<_>>());
+}
@@ -0,0 +1,50 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use okay::header::Channels;
+use okay::{Decoder, Encoder, Header, Pixel};
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures");
+    let mut paths = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "qoi"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+fn decode(bytes: &[u8]) -> (Header, Vec<Pixel>) {
+    let (header, decoder) = Decoder::new_from_slice(bytes).decode_header().unwrap();
+    (header, decoder.decode_pixels_vec_checked().unwrap())
+}
+
+#[test]
+fn round_trip_fixtures() {
+    let paths = fixtures();
+    assert!(!paths.is_empty(), "no fixtures found");
+
+    for path in &paths {
+        let (header, pixels) = decode(&fs::read(path).unwrap());
+
+        let encoded = Encoder::encode_to_vec(header.clone(), &pixels).unwrap();
+        let (decoded_header, decoded) = decode(&encoded);
+        assert_eq!(decoded_header, header, "{}", path.display());
+        assert!(decoded == pixels, "{}: pixels differ after round trip", path.display());
+
+        // The re-encoded image should also be accepted by the reference decoder
+        let (reference_header, reference) = qoi::decode_to_vec(&encoded).unwrap();
+        assert_eq!(
+            (reference_header.width, reference_header.height),
+            header.dimensions(),
+            "{}",
+            path.display()
+        );
+        let expected = match header.channels() {
+            Channels::Rgb => pixels.iter().flat_map(|pixel| pixel.rgb()).collect::<Vec<_>>(),
+            Channels::Rgba => pixels.iter().flat_map(|pixel| pixel.rgba()).collect::<Vec<_>>(),
+        };
+        assert!(reference == expected, "{}: reference decoder disagrees", path.display());
+    }
+}
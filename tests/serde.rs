@@ -0,0 +1,32 @@
+#![cfg(feature = "serde")]
+
+use okay::header::{Channels, ColSpace};
+use okay::{Header, Pixel};
+
+#[test]
+fn header_round_trip() {
+    let header = Header::new(640, 480, Channels::Rgba, ColSpace::Linear);
+    let json = serde_json::to_string(&header).unwrap();
+    assert_eq!(
+        json,
+        r#"{"width":640,"height":480,"channels":"rgba","col_space":"linear"}"#
+    );
+    assert_eq!(serde_json::from_str::<Header>(&json).unwrap(), header);
+}
+
+#[test]
+fn channels_and_col_space_strings() {
+    assert_eq!(serde_json::to_string(&Channels::Rgb).unwrap(), r#""rgb""#);
+    assert_eq!(serde_json::to_string(&ColSpace::Srgb).unwrap(), r#""srgb""#);
+    assert_eq!(serde_json::from_str::<Channels>(r#""rgba""#).unwrap(), Channels::Rgba);
+    assert!(serde_json::from_str::<ColSpace>(r#""SRGB""#).is_err());
+}
+
+#[test]
+fn pixel_round_trip() {
+    let pixel = Pixel::new(1, 2, 3, 4);
+    let json = serde_json::to_string(&pixel).unwrap();
+    assert_eq!(json, r#"{"r":1,"g":2,"b":3,"a":4}"#);
+    assert_eq!(serde_json::from_str::<Pixel>(&json).unwrap(), pixel);
+    assert_eq!(serde_json::from_str::<Pixel>("[1,2,3,4]").unwrap(), pixel);
+}
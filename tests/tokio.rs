@@ -0,0 +1,118 @@
+#![cfg(feature = "tokio")]
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use okay::byte_stream::StreamError;
+use okay::decode::HeaderDecodeError;
+use okay::{Decoder, Pixel};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// An async reader which returns at most `max_len` bytes per read.
+struct Trickle<'a> {
+    bytes: &'a [u8],
+    max_len: usize,
+}
+
+impl<'a> AsyncRead for Trickle<'a> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let len = self.bytes.len().min(self.max_len).min(buf.remaining());
+        buf.put_slice(&self.bytes[..len]);
+        self.bytes = &self.bytes[len..];
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn test_image() -> Vec<u8> {
+    let rgba = (0..600u32)
+        .flat_map(|i| {
+            let v = (i / 3) as u8;
+            [v, v / 2, 255 - v, if i % 100 < 50 { 255 } else { v }]
+        })
+        .collect::<Vec<_>>();
+    qoi::encode_to_vec(&rgba, 30, 20).unwrap()
+}
+
+#[tokio::test]
+async fn decode_async() {
+    let bytes = test_image();
+    let (expected_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let expected = decoder.decode_pixels_vec().unwrap();
+
+    for max_len in [1, 3, 7, 4096] {
+        let reader = Trickle { bytes: &bytes, max_len };
+        let (header, mut decoder) = Decoder::new_from_async_reader(reader)
+            .decode_header_async()
+            .await
+            .unwrap();
+        assert_eq!(header, expected_header);
+
+        let mut pixels = vec![Pixel::ZERO; 600];
+        assert_eq!(decoder.decode_pixels_into_async(&mut pixels[..250]).await.unwrap(), (250, false));
+        assert_eq!(decoder.decode_pixels_into_async(&mut pixels[250..]).await.unwrap(), (350, true));
+        assert_eq!(pixels, expected);
+    }
+}
+
+#[tokio::test]
+async fn decode_async_truncated() {
+    let bytes = test_image();
+    let reader = Trickle { bytes: &bytes[..100], max_len: 16 };
+    let (_header, mut decoder) = Decoder::new_from_async_reader(reader)
+        .decode_header_async()
+        .await
+        .unwrap();
+
+    let mut pixels = vec![Pixel::ZERO; 600];
+    let decoded = match decoder.decode_pixels_into_async(&mut pixels).await {
+        Err((decoded, StreamError::UnexpectedEof)) => decoded,
+        res => panic!("expected UnexpectedEof, got {:?}", res),
+    };
+    assert!(decoded > 0);
+    assert_eq!(decoder.remaining_pixels(), 600 - decoded as u64);
+
+    let reader = Trickle { bytes: &bytes[..10], max_len: 16 };
+    assert!(matches!(
+        Decoder::new_from_async_reader(reader).decode_header_async().await,
+        Err(HeaderDecodeError::UnexpectedEof)
+    ));
+}
+
+#[tokio::test]
+async fn decode_async_resume_after_eof() {
+    let bytes = test_image();
+    let (_header, decoder) = Decoder::new_from_slice(&bytes).decode_header().unwrap();
+    let expected = decoder.decode_pixels_vec().unwrap();
+
+    for split in [14, 15, 100, 333, bytes.len() - 8] {
+        let reader = Trickle { bytes: &bytes[..split], max_len: 7 };
+        let (_header, mut decoder) = Decoder::new_from_async_reader(reader)
+            .decode_header_async()
+            .await
+            .unwrap();
+
+        let mut pixels = vec![Pixel::ZERO; 600];
+        let decoded = match decoder.decode_pixels_into_async(&mut pixels).await {
+            Ok((decoded, true)) => decoded,
+            Err((decoded, StreamError::UnexpectedEof)) => {
+                // More bytes arrive after the reader first runs out
+                decoder.stream_mut().inner_mut().bytes = &bytes[split..];
+                let (n, finished) = decoder
+                    .decode_pixels_into_async(&mut pixels[decoded..])
+                    .await
+                    .unwrap();
+                assert!(finished);
+                decoded + n
+            }
+            res => panic!("unexpected result: {:?}", res),
+        };
+
+        assert_eq!(decoded, 600);
+        assert_eq!(pixels, expected);
+    }
+}
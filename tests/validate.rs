@@ -0,0 +1,109 @@
+use okay::header::{Channels, ColSpace};
+use okay::validate::ValidationIssue;
+use okay::{Decoder, Header};
+
+mod common;
+
+use common::qoi_bytes;
+
+fn issues(bytes: &[u8]) -> Vec<ValidationIssue> {
+    let report = Decoder::new_from_slice(bytes).decode_validated(true).unwrap();
+    report.issues().to_vec()
+}
+
+#[test]
+fn reference_encoder_is_valid() {
+    let mut state = 0x9E37_79B9u32;
+    let rgba = (0..(31 * 17))
+        .flat_map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let [a, b, c, d] = state.to_be_bytes();
+            match a % 5 {
+                0 => [b, c, d, 255],
+                1 => [b, c, d, a],
+                2 => [b % 2, c % 2, 0, 255],
+                _ => [0, 0, 0, 255],
+            }
+        })
+        .collect::<Vec<_>>();
+    let bytes = qoi::encode_to_vec(&rgba, 31, 17).unwrap();
+
+    let report = Decoder::new_from_slice(&bytes).decode_validated(true).unwrap();
+    assert!(report.is_valid(), "{:?}", report.issues());
+    assert_eq!(report.header(), Some(&Header::new(31, 17, Channels::Rgba, ColSpace::Srgb)));
+}
+
+#[test]
+fn reports_every_issue() {
+    let mut bytes = qoi_bytes(2, 1, 4, &[0xFE, 100, 150, 200, 0xC0]);
+    bytes[3] = b'g';
+    bytes[12] = 5;
+    bytes.extend_from_slice(&[1, 2, 3]);
+
+    let report = Decoder::new_from_slice(&bytes).decode_validated(false).unwrap();
+    assert_eq!(report.header(), None);
+
+    let issues = report.issues();
+    assert_eq!(issues.len(), 3);
+    assert!(matches!(issues[0], ValidationIssue::Magic(_)));
+    assert!(matches!(issues[1], ValidationIssue::Channels(_)));
+    assert_eq!(issues[2], ValidationIssue::TrailingBytes { offset: 27, len: 3 });
+}
+
+#[test]
+fn truncated() {
+    assert_eq!(issues(b"qoif\0\0"), [ValidationIssue::TruncatedHeader]);
+
+    let bytes = qoi_bytes(3, 1, 4, &[0xFE, 100, 150, 200]);
+    assert_eq!(
+        issues(&bytes[..16]),
+        [
+            ValidationIssue::TruncatedPixels { offset: 14, expected: 3, found: 0 },
+        ]
+    );
+}
+
+#[test]
+fn bad_end_marker() {
+    let mut bytes = qoi_bytes(1, 1, 4, &[0xFE, 100, 150, 200]);
+    let len = bytes.len();
+    bytes[len - 1] = 0;
+    assert_eq!(
+        issues(&bytes),
+        [ValidationIssue::BadEndMarker { offset: 18, found: vec![0; 8] }]
+    );
+
+    bytes.truncate(len - 3);
+    assert_eq!(
+        issues(&bytes),
+        [ValidationIssue::BadEndMarker { offset: 18, found: vec![0; 5] }]
+    );
+}
+
+#[test]
+fn run_past_end() {
+    let bytes = qoi_bytes(2, 2, 4, &[0xFE, 100, 150, 200, 0xC4]);
+    assert_eq!(issues(&bytes), [ValidationIssue::RunPastEnd { offset: 18, excess: 2 }]);
+}
+
+#[test]
+fn non_canonical() {
+    let chunks = [
+        // RGBA where RGB would do, since alpha is unchanged
+        0xFF, 100, 150, 200, 255,
+        // RGB which repeats the previous pixel, instead of a run
+        0xFE, 100, 150, 200,
+        // Two short runs in a row
+        0xC0, 0xC0,
+    ];
+    let bytes = qoi_bytes(4, 1, 4, &chunks);
+    assert_eq!(
+        issues(&bytes),
+        [ValidationIssue::NonCanonical { offset: 14, pixel: 0, count: 3 }]
+    );
+
+    let report = Decoder::new_from_slice(&bytes).decode_validated(false).unwrap();
+    assert!(report.is_valid());
+}
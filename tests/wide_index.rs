@@ -0,0 +1,97 @@
+#![cfg(feature = "wide_index")]
+
+use okay::decode::HeaderDecodeError;
+use okay::header::{Channels, ColSpace};
+use okay::{Decoder, Encoder, Header, Pixel};
+
+fn encode_wide<const N: usize>(header: Header, pixels: &[Pixel]) -> Vec<u8> {
+    let mut encoder = Encoder::new_to_vec(header).encode_header_wide::<N>().unwrap();
+    encoder.encode_pixels(pixels).unwrap();
+    encoder.finish().unwrap().into_inner()
+}
+
+fn decode_wide<const N: usize>(bytes: &[u8]) -> (Header, Vec<Pixel>) {
+    let (header, decoder) = Decoder::new_from_slice(bytes).decode_header_wide::<N>().unwrap();
+    (header, decoder.decode_pixels_vec().unwrap())
+}
+
+/// An image which cycles through more colors than fit in a standard index, with alpha changes so
+/// that any color which misses the index needs a five-byte QOI_OP_RGBA chunk.
+fn many_colors() -> Vec<Pixel> {
+    (0..2000u32)
+        .map(|i| {
+            let c = (i * 37) % 200;
+            Pixel::new(c as u8, (c * 3) as u8, (c * 7) as u8, 100 + (c % 2) as u8 * 100)
+        })
+        .collect()
+}
+
+#[test]
+fn wide_standard_size_matches_qoi() {
+    let header = Header::new(50, 40, Channels::Rgba, ColSpace::Srgb);
+    let pixels = many_colors();
+    let bytes = encode_wide::<64>(header.clone(), &pixels);
+
+    assert_eq!(&bytes[..4], b"qoiw");
+    assert_eq!(&bytes[4..14], &header.to_bytes()[4..]);
+    assert_eq!(bytes[14], 6);
+
+    // With 64 slots, only the header differs from a standard file
+    let standard = Encoder::encode_to_vec(header.clone(), &pixels).unwrap();
+    assert_eq!(&bytes[15..], &standard[14..]);
+
+    assert_eq!(decode_wide::<64>(&bytes), (header, pixels));
+}
+
+#[test]
+fn wide_round_trip() {
+    let header = Header::new(50, 40, Channels::Rgba, ColSpace::Linear);
+    let pixels = many_colors();
+    let standard = Encoder::encode_to_vec(header.clone(), &pixels).unwrap();
+
+    let bytes = encode_wide::<256>(header.clone(), &pixels);
+    assert_eq!(bytes[14], 8);
+    assert_eq!(decode_wide::<256>(&bytes), (header.clone(), pixels.clone()));
+
+    // Most colors after the first cycle are found in the larger index, so the file is much smaller
+    assert!(bytes.len() < standard.len() / 2);
+
+    let bytes = encode_wide::<8192>(header.clone(), &pixels);
+    assert_eq!(decode_wide::<8192>(&bytes), (header.clone(), pixels.clone()));
+
+    let bytes = encode_wide::<16>(header.clone(), &pixels);
+    assert_eq!(decode_wide::<16>(&bytes), (header, pixels));
+}
+
+#[test]
+fn wide_rgb_and_runs() {
+    let header = Header::new(10, 10, Channels::Rgb, ColSpace::Srgb);
+    let pixels = (0..100u8)
+        .map(|i| Pixel::new(i / 10 * 25, 255 - i / 10, 3, 255))
+        .collect::<Vec<_>>();
+    let bytes = encode_wide::<1024>(header.clone(), &pixels);
+    assert_eq!(decode_wide::<1024>(&bytes), (header, pixels));
+}
+
+#[test]
+fn wide_index_size_mismatch() {
+    let header = Header::new(2, 1, Channels::Rgba, ColSpace::Srgb);
+    let bytes = encode_wide::<256>(header, &[Pixel::BLACK, Pixel::ZERO]);
+
+    assert!(matches!(
+        Decoder::new_from_slice(&bytes).decode_header_wide::<512>(),
+        Err(HeaderDecodeError::IndexSize { expected: 9, found: 8 })
+    ));
+
+    assert!(matches!(
+        Decoder::new_from_slice(&bytes).decode_header(),
+        Err(HeaderDecodeError::Magic(_))
+    ));
+
+    let header = Header::new(1, 1, Channels::Rgba, ColSpace::Srgb);
+    let standard = Encoder::encode_to_vec(header, &[Pixel::BLACK]).unwrap();
+    assert!(matches!(
+        Decoder::new_from_slice(&standard).decode_header_wide::<64>(),
+        Err(HeaderDecodeError::NotWide { magic }) if &magic == b"qoif"
+    ));
+}